@@ -1,121 +1,525 @@
-use std::{num::NonZeroU16, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    num::NonZeroU16,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
-use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use parking_lot::Mutex;
 
-use self::serenity::{
-    json::{json, to_vec},
-    UserId,
-};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+
+use self::serenity::{json::json, UserId};
 use poise::serenity_prelude as serenity;
 
-use crate::{require, structs::BotListTokens, Result};
+use crate::{structs::BotListTokens, Looper as _, Result};
+
+/// How many times to attempt each bot list request before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+/// How long to wait before the first retry, doubled for each retry after.
+const BASE_BACKOFF: Duration = Duration::from_secs(2);
+/// How many guild count samples to keep, a day's worth at the default interval.
+const HISTORY_SIZE: usize = 24;
+/// The longest random delay before each update, so shards do not all update at once.
+const MAX_JITTER: Duration = Duration::from_secs(5 * 60);
+/// The shortest time between two updates, such as a manual update straight after the loop.
+const MIN_UPDATE_GAP: Duration = Duration::from_secs(60);
 
 pub struct BotListUpdater {
     cache: Arc<serenity::cache::Cache>,
+    shard_manager: Arc<serenity::ShardManager>,
     reqwest: reqwest::Client,
     tokens: BotListTokens,
+    lists: BotLists,
+    /// The guild count at each loop, oldest first.
+    history: Mutex<VecDeque<(SystemTime, usize)>>,
+    /// Logs the requests instead of sending them, for local development.
+    dry_run: bool,
+    /// Bot lists that have rate limited us, and when we can next send to them.
+    rate_limited_until: Mutex<HashMap<&'static str, Instant>>,
+    /// When the last successful update was started, see [`Self::update_now`].
+    last_update: Mutex<Option<Instant>>,
 }
 
-struct BotListReq {
-    url: String,
+/// The bot lists with a token set, built at startup so only the body changes each loop.
+struct BotLists {
+    top_gg: Option<BotList>,
+    discord_bots_gg: Option<BotList>,
+    bots_on_discord: Option<BotList>,
+    discords_com: Option<BotList>,
+}
+
+/// A bot list's endpoint, with the token validated as a header value.
+struct BotList {
+    name: &'static str,
+    url: reqwest::Url,
+    headers: HeaderMap,
+}
+
+impl BotList {
+    /// Returns `None` if there is no token, or if it is invalid, so one bad token in the config
+    /// only skips that list instead of stopping the other lists being updated.
+    fn new(name: &'static str, url: &str, token: Option<&str>) -> Option<Self> {
+        let token = match HeaderValue::from_str(token?) {
+            Ok(token) => token,
+            Err(err) => {
+                tracing::error!("Invalid bot list token for {name}, skipping it: {err}");
+                return None;
+            }
+        };
+
+        let url = match reqwest::Url::parse(url) {
+            Ok(url) => url,
+            Err(err) => {
+                tracing::error!("Invalid bot list URL for {name}, skipping it: {err}");
+                return None;
+            }
+        };
+
+        Some(Self {
+            name,
+            url,
+            headers: HeaderMap::from_iter([
+                (AUTHORIZATION, token),
+                (CONTENT_TYPE, HeaderValue::from_static("application/json")),
+            ]),
+        })
+    }
+
+    fn request(&self, body: &serenity::json::Value) -> BotListReq<'_> {
+        BotListReq {
+            list: self,
+            body: body.to_string().into_bytes(),
+        }
+    }
+}
+
+struct BotListReq<'a> {
+    list: &'a BotList,
     body: Vec<u8>,
-    token: HeaderValue,
 }
 
+/// The rate limit headers sent back by a bot list, if any.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RateLimit {
+    pub retry_after: Option<Duration>,
+    pub remaining: Option<u64>,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let parse_header = |name| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+        Self {
+            // Only the delay-seconds form is handled, none of the lists send a HTTP date
+            retry_after: parse_header(RETRY_AFTER.as_str()).map(Duration::from_secs),
+            remaining: parse_header("x-ratelimit-remaining"),
+        }
+    }
+}
+
+/// A failed update to a single bot list.
+#[derive(Debug)]
+pub struct BotListError {
+    pub name: &'static str,
+    pub status: Option<reqwest::StatusCode>,
+    pub rate_limit: RateLimit,
+    pub source: reqwest::Error,
+}
+
+/// Every bot list that failed to update in a single loop.
+#[derive(Debug)]
+pub struct BotListErrors(pub Vec<BotListError>);
+
+impl fmt::Display for BotListErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to update {} bot list(s)", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n{}", error.name)?;
+            if let Some(status) = error.status {
+                write!(f, " ({status})")?;
+            }
+
+            if let Some(retry_after) = error.rate_limit.retry_after {
+                write!(f, " [retry after {retry_after:?}]")?;
+            }
+
+            if let Some(remaining) = error.rate_limit.remaining {
+                write!(f, " [{remaining} requests remaining]")?;
+            }
+
+            write!(f, ": {}", error.source)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for BotListErrors {}
+
 impl BotListUpdater {
-    #[must_use]
+    /// Errors if any of the tokens are not valid header values, such as containing a newline.
+    ///
+    /// Must be called after the ready event, as the URLs contain the bot's user ID.
     pub fn new(
         reqwest: reqwest::Client,
         cache: Arc<serenity::cache::Cache>,
+        shard_manager: Arc<serenity::ShardManager>,
         tokens: BotListTokens,
+        dry_run: bool,
     ) -> Self {
+        let bot_id: UserId = cache.current_user().id;
+        let lists = BotLists {
+            top_gg: BotList::new(
+                "top.gg",
+                &format!("https://top.gg/api/bots/{bot_id}/stats"),
+                tokens.top_gg.as_deref(),
+            ),
+            discord_bots_gg: BotList::new(
+                "discord.bots.gg",
+                &format!("https://discord.bots.gg/api/v1/bots/{bot_id}/stats"),
+                tokens.discord_bots_gg.as_deref(),
+            ),
+            bots_on_discord: BotList::new(
+                "bots.ondiscord.xyz",
+                &format!("https://bots.ondiscord.xyz/bot-api/bots/{bot_id}/guilds"),
+                tokens.bots_on_discord.as_deref(),
+            ),
+            discords_com: BotList::new(
+                "discords.com",
+                &format!("https://discords.com/bots/api/bot/{bot_id}"),
+                tokens.discords_com.as_deref(),
+            ),
+        };
+
         Self {
             cache,
+            shard_manager,
             reqwest,
             tokens,
+            lists,
+            history: Mutex::new(VecDeque::with_capacity(HISTORY_SIZE)),
+            dry_run,
+            rate_limited_until: Mutex::new(HashMap::new()),
+            last_update: Mutex::new(None),
+        }
+    }
+
+    /// Why the stats should not be posted yet, such as shards still connecting after a restart.
+    async fn not_ready_reason(&self, shard_count: NonZeroU16) -> Option<String> {
+        let connected_shards = self
+            .shard_manager
+            .runners
+            .lock()
+            .await
+            .values()
+            .filter(|info| info.stage == serenity::ConnectionStage::Connected)
+            .count();
+
+        if connected_shards < usize::from(shard_count.get()) {
+            return Some(format!("{connected_shards}/{shard_count} shards connected"));
+        }
+
+        let unavailable_guilds = self.cache.unavailable_guilds().len();
+        if unavailable_guilds != 0 {
+            return Some(format!("{unavailable_guilds} guilds still loading"));
         }
+
+        if self.cache.guild_count() == 0 {
+            return Some(String::from("no guilds cached"));
+        }
+
+        None
     }
 
-    fn top_gg_data(
+    /// If `name` rate limited us recently, and the retry window has not passed yet.
+    fn is_rate_limited(&self, name: &'static str) -> bool {
+        let mut rate_limited_until = self.rate_limited_until.lock();
+        match rate_limited_until.get(name) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                rate_limited_until.remove(name);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn record_rate_limit(&self, error: &BotListError) {
+        if let Some(retry_after) = error.rate_limit.retry_after {
+            let until = Instant::now() + retry_after;
+            self.rate_limited_until.lock().insert(error.name, until);
+        }
+    }
+
+    /// The last [`HISTORY_SIZE`] guild counts and when they were taken, oldest first.
+    pub fn guild_count_history(&self) -> Vec<(SystemTime, usize)> {
+        self.history.lock().iter().copied().collect()
+    }
+
+    fn record_guild_count(&self, guild_count: usize) {
+        let mut history = self.history.lock();
+        if history.len() == HISTORY_SIZE {
+            history.pop_front();
+        }
+
+        history.push_back((SystemTime::now(), guild_count));
+    }
+
+    /// Counts the cached guilds on each shard, indexed by shard ID.
+    ///
+    /// Returns `None` if no guilds have been cached yet, so the aggregate count is used.
+    fn shard_guild_counts(&self, shard_count: NonZeroU16) -> Option<Vec<usize>> {
+        let guild_ids = self.cache.guilds();
+        if guild_ids.is_empty() {
+            return None;
+        }
+
+        let shard_count = u64::from(shard_count.get());
+        let mut counts = vec![0; shard_count as usize];
+        for guild_id in guild_ids {
+            counts[((guild_id.get() >> 22) % shard_count) as usize] += 1;
+        }
+
+        Some(counts)
+    }
+
+    fn top_gg_data(&self, guild_count: usize, shard_count: NonZeroU16) -> Option<BotListReq<'_>> {
+        self.lists.top_gg.as_ref().map(|list| {
+            let body = match self.shard_guild_counts(shard_count) {
+                Some(shards) => json!({
+                    "server_count": guild_count,
+                    "shard_count": shard_count,
+                    "shards": shards,
+                }),
+                None => json!({
+                    "server_count": guild_count,
+                    "shard_count": shard_count,
+                }),
+            };
+
+            list.request(&body)
+        })
+    }
+
+    fn discords_com_data(&self, guild_count: usize) -> Option<BotListReq<'_>> {
+        let list = self.lists.discords_com.as_ref()?;
+        Some(list.request(&json!({"server_count": guild_count})))
+    }
+
+    fn discord_bots_gg_data(
         &self,
-        bot_id: UserId,
         guild_count: usize,
         shard_count: NonZeroU16,
-    ) -> Option<BotListReq> {
-        self.tokens.top_gg.as_deref().map(|token| BotListReq {
-            url: format!("https://top.gg/api/bots/{bot_id}/stats"),
-            token: HeaderValue::from_str(token).unwrap(),
-            body: to_vec(&json!({
-                "server_count": guild_count,
-                "shard_count": shard_count,
-            }))
-            .unwrap(),
-        })
+    ) -> Option<BotListReq<'_>> {
+        let list = self.lists.discord_bots_gg.as_ref()?;
+        Some(list.request(&json!({
+            "guildCount": guild_count,
+            "shardCount": shard_count,
+        })))
     }
 
-    fn discord_bots_gg_data(
+    fn bots_on_discord_data(&self, guild_count: usize) -> Option<BotListReq<'_>> {
+        let list = self.lists.bots_on_discord.as_ref()?;
+        Some(list.request(&json!({"guildCount": guild_count})))
+    }
+
+    /// Sends the stats snapshot to the configured stats webhook, if set.
+    async fn post_stats_webhook(
         &self,
         bot_id: UserId,
         guild_count: usize,
         shard_count: NonZeroU16,
-    ) -> Option<BotListReq> {
-        self.tokens
-            .discord_bots_gg
-            .as_deref()
-            .map(|token| BotListReq {
-                url: format!("https://discord.bots.gg/api/v1/bots/{bot_id}/stats"),
-                token: HeaderValue::from_str(token).unwrap(),
-                body: to_vec(&json!({
-                    "guildCount": guild_count,
-                    "shardCount": shard_count,
-                }))
-                .unwrap(),
-            })
-    }
-
-    fn bots_on_discord_data(&self, bot_id: UserId, guild_count: usize) -> Option<BotListReq> {
-        self.tokens
-            .bots_on_discord
-            .as_deref()
-            .map(|token| BotListReq {
-                url: format!("https://bots.ondiscord.xyz/bot-api/bots/{bot_id}/guilds"),
-                body: to_vec(&json!({"guildCount": guild_count})).unwrap(),
-                token: HeaderValue::from_str(token).unwrap(),
-            })
+    ) -> Result<(), BotListError> {
+        let Some(url) = &self.tokens.stats_webhook else {
+            return Ok(());
+        };
+
+        let body = json!({
+            "bot_id": bot_id,
+            "guild_count": guild_count,
+            "shard_count": shard_count,
+        })
+        .to_string();
+
+        if self.dry_run {
+            tracing::debug!("Dry run, not sending to stats webhook: {url} {body}");
+            return Ok(());
+        }
+
+        let build_request = || {
+            self.reqwest
+                .post(url.clone())
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .body(body.clone())
+        };
+
+        send_with_retry("stats webhook", build_request).await
     }
-}
 
-impl crate::Looper for BotListUpdater {
-    const NAME: &'static str = "Bot List Updater";
-    const MILLIS: u64 = 1000 * 60 * 60;
+    /// Sends the current stats to every bot list straight away, instead of waiting for the loop.
+    ///
+    /// Returns `false` without sending anything if the bot is still starting up, or if an update
+    /// was sent within the last [`MIN_UPDATE_GAP`], so concurrent calls cannot double up.
+    pub async fn update_now(&self) -> Result<bool> {
+        let perform = |req: Option<BotListReq<'_>>| async move {
+            let Some(BotListReq { list, body }) = req else {
+                return Ok(());
+            };
 
-    async fn loop_func(&self) -> Result<()> {
-        let perform = |req| async move {
-            if let Some(BotListReq { url, body, token }) = req {
-                let headers = reqwest::header::HeaderMap::from_iter([
-                    (AUTHORIZATION, token),
-                    (CONTENT_TYPE, HeaderValue::from_static("application/json")),
-                ]);
+            let name = list.name;
+            if self.dry_run {
+                let body = String::from_utf8_lossy(&body);
+                tracing::debug!("Dry run, not sending to {name}: {} {body}", list.url);
+                return Ok(());
+            }
 
-                let request = self.reqwest.post(url).body(body).headers(headers);
+            if self.is_rate_limited(name) {
+                tracing::info!("Skipping {name} update, still rate limited");
+                return Ok(());
+            }
 
-                let err = require!(match request.send().await {
-                    Ok(resp) => resp.error_for_status().err(),
-                    Err(err) => Some(err),
-                });
+            let build_request = || {
+                self.reqwest
+                    .post(list.url.clone())
+                    .body(body.clone())
+                    .headers(list.headers.clone())
+            };
 
-                tracing::error!("{} Error: {:?}", Self::NAME, err);
+            let result = send_with_retry(name, build_request).await;
+            if let Err(err) = &result {
+                self.record_rate_limit(err);
             }
+
+            result
         };
 
         let shard_count = self.cache.shard_count();
+        if let Some(reason) = self.not_ready_reason(shard_count).await {
+            tracing::debug!("{}: Skipping update, {reason}", Self::NAME);
+            return Ok(false);
+        }
+
+        // Reserved before sending so concurrent calls cannot double up, then put back on failure.
+        let previous_update = {
+            let mut last_update = self.last_update.lock();
+            if last_update.is_some_and(|last| last.elapsed() < MIN_UPDATE_GAP) {
+                tracing::debug!("{}: Skipping update, one was just sent", Self::NAME);
+                return Ok(false);
+            }
+
+            last_update.replace(Instant::now())
+        };
+
         let bot_id = self.cache.current_user().id;
         let guild_count = self.cache.guild_count();
+        self.record_guild_count(guild_count);
 
-        perform(self.bots_on_discord_data(bot_id, guild_count)).await;
-        perform(self.top_gg_data(bot_id, guild_count, shard_count)).await;
-        perform(self.discord_bots_gg_data(bot_id, guild_count, shard_count)).await;
+        // Run concurrently, so one list's retries do not delay the others
+        let (bots_on_discord, top_gg, discord_bots_gg, discords_com) = tokio::join!(
+            perform(self.bots_on_discord_data(guild_count)),
+            perform(self.top_gg_data(guild_count, shard_count)),
+            perform(self.discord_bots_gg_data(guild_count, shard_count)),
+            perform(self.discords_com_data(guild_count)),
+        );
 
-        Ok(())
+        // Sent after the lists, so a broken webhook cannot hold up the real updates
+        let stats_webhook = self
+            .post_stats_webhook(bot_id, guild_count, shard_count)
+            .await;
+
+        let results = [
+            bots_on_discord,
+            top_gg,
+            discord_bots_gg,
+            discords_com,
+            stats_webhook,
+        ];
+
+        let errors: Vec<_> = results
+            .into_iter()
+            .filter_map(std::result::Result::err)
+            .collect();
+        if errors.is_empty() {
+            Ok(true)
+        } else {
+            // A failed update should be retried straight away, not blocked by the gap.
+            *self.last_update.lock() = previous_update;
+            Err(BotListErrors(errors).into())
+        }
+    }
+}
+
+fn is_retryable(err: &reqwest::Error) -> bool {
+    // 4xx errors are usually a bad token, so retrying will not help
+    err.is_connect() || err.is_timeout() || err.status().is_some_and(|s| s.is_server_error())
+}
+
+/// Sends the request built by `build_request`, keeping the rate limit headers on failure.
+async fn send_once(
+    build_request: &impl Fn() -> reqwest::RequestBuilder,
+) -> Result<(), (reqwest::Error, RateLimit)> {
+    let response = build_request()
+        .send()
+        .await
+        .map_err(|err| (err, RateLimit::default()))?;
+
+    let rate_limit = RateLimit::from_headers(response.headers());
+    response
+        .error_for_status()
+        .map(drop)
+        .map_err(|err| (err, rate_limit))
+}
+
+/// Sends the request built by `build_request`, retrying connection errors and 5xx
+/// responses up to [`MAX_ATTEMPTS`] times with exponential backoff.
+async fn send_with_retry(
+    name: &'static str,
+    build_request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<(), BotListError> {
+    let mut backoff = BASE_BACKOFF;
+    let mut result = send_once(&build_request).await;
+    for _ in 1..MAX_ATTEMPTS {
+        match &result {
+            Err((err, _)) if is_retryable(err) => {
+                tracing::warn!("Bot list request failed, retrying in {backoff:?}: {err:?}");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            _ => break,
+        }
+
+        result = send_once(&build_request).await;
+    }
+
+    result.map_err(|(source, rate_limit)| BotListError {
+        name,
+        status: source.status(),
+        rate_limit,
+        source,
+    })
+}
+
+impl crate::Looper for BotListUpdater {
+    const NAME: &'static str = "Bot List Updater";
+    const MILLIS: u64 = 1000 * 60 * 60;
+
+    fn interval(&self) -> Duration {
+        self.tokens.update_interval.map_or_else(
+            || Duration::from_millis(Self::MILLIS),
+            |mins| Duration::from_secs(mins.get() * 60),
+        )
+    }
+
+    fn max_jitter(&self) -> Duration {
+        if self.tokens.disable_jitter {
+            Duration::ZERO
+        } else {
+            MAX_JITTER
+        }
+    }
+
+    async fn loop_func(&self) -> Result<()> {
+        self.update_now().await.map(drop)
     }
 }
@@ -14,38 +14,164 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use songbird::error::JoinError;
+use std::{borrow::Cow, fmt::Write as _, time::Duration};
 
-use poise::serenity_prelude::{self as serenity, builder::*};
+use songbird::tracks::{TrackQueue, TrackResult};
+
+use itertools::Itertools as _;
+
+use poise::serenity_prelude::{self as serenity, builder::*, Mentionable as _};
 
 use crate::{
-    funcs::random_footer,
+    constants,
+    database::GuildRow,
+    funcs::{self, is_emoji_usable, random_footer},
+    opt_ext::OptionTryUnwrap,
     require, require_guild,
     structs::{Command, CommandResult, Context, JoinVCToken, Result},
-    traits::{PoiseContextExt, SongbirdManagerExt},
+    traits::{ChannelSource, CheckResponder, JoinVCError, PoiseContextExt, SongbirdManagerExt},
 };
 
+/// Queues longer than this need confirming before `/clear` stops them.
+const CLEAR_CONFIRM_THRESHOLD: usize = 10;
+
+/// How long to wait for Discord when fetching an uncached guild over HTTP.
+const UNCACHED_GUILD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Filters `channel_ids` to those in the guild, falling back to HTTP if the guild is not cached yet.
+///
+/// Returns `None` if the guild is not cached and Discord did not respond in time.
+async fn existing_channels(
+    ctx: &Context<'_>,
+    channel_ids: Vec<serenity::ChannelId>,
+) -> Result<Option<Vec<serenity::ChannelId>>> {
+    let existing = existing_channels_with(ctx, channel_ids, UNCACHED_GUILD_TIMEOUT).await?;
+    if existing.is_none() {
+        let guild_id = ctx.guild_id().unwrap();
+        tracing::warn!("Timed out fetching channels for uncached guild {guild_id}");
+    }
+
+    Ok(existing)
+}
+
+/// [`existing_channels`], looking up the guild's channels from `source`.
+async fn existing_channels_with(
+    source: &impl ChannelSource,
+    mut channel_ids: Vec<serenity::ChannelId>,
+    timeout: Duration,
+) -> Result<Option<Vec<serenity::ChannelId>>> {
+    if channel_ids.is_empty() {
+        return Ok(Some(channel_ids));
+    }
+
+    let guild_channels = match source.cached_channel_ids() {
+        Some(guild_channels) => guild_channels,
+        None => match tokio::time::timeout(timeout, source.fetch_channel_ids()).await {
+            Ok(guild_channels) => guild_channels?,
+            Err(_) => return Ok(None),
+        },
+    };
+
+    channel_ids.retain(|id| guild_channels.contains(id));
+    Ok(Some(channel_ids))
+}
+
 async fn channel_check(ctx: &Context<'_>, author_vc: Option<serenity::ChannelId>) -> Result<bool> {
     let guild_id = ctx.guild_id().unwrap();
-    let setup_id = ctx.data().guilds_db.get(guild_id.into()).await?.channel;
+    // Served from the guilds_db cache, which `/setup` invalidates via `set_one`.
+    let guild_row = ctx.data().guilds_db.get(guild_id.into()).await?;
 
-    let channel_id = Some(ctx.channel_id());
-    if setup_id == channel_id || author_vc == channel_id {
-        return Ok(true);
+    let check = ctx
+        .guild()
+        .map(|guild| channel_check_in(ctx, &guild, author_vc, &guild_row));
+
+    if let Some(check) = check {
+        return check.await;
     }
 
-    let msg = if let Some(setup_id) = setup_id
-        && require_guild!(ctx, Ok(false))
-            .channels
-            .contains_key(&setup_id)
+    // The guild only needs to be fetched if the check is going to fail.
+    let channel_id = ctx.channel_id();
+    let existing_setup = if guild_row.is_setup_channel(channel_id) || author_vc == Some(channel_id)
     {
-        ctx.gettext("You ran this command in the wrong channel, please move to <#{channel_id}>.")
-            .replace("{channel_id}", &setup_id.to_string())
+        Vec::new()
+    } else if let Some(existing_setup) =
+        existing_channels(ctx, guild_row.setup_channels().collect()).await?
+    {
+        existing_setup
     } else {
-        String::from(ctx.gettext("You haven't setup the bot, please run /setup!"))
+        let msg = ctx.gettext("I'm still loading this server, try again in a moment!");
+        ctx.send_error(msg.to_owned()).await?;
+        return Ok(false);
     };
 
-    ctx.send_error(msg).await?;
+    channel_check_with(ctx, author_vc, &guild_row, &existing_setup).await
+}
+
+/// [`channel_check`] for callers which have already read the guild from the cache.
+///
+/// The guild is only read up front, so the cache is not held while responding.
+fn channel_check_in<'a, 'ctx>(
+    ctx: &'a Context<'ctx>,
+    guild: &serenity::Guild,
+    author_vc: Option<serenity::ChannelId>,
+    guild_row: &'a GuildRow,
+) -> impl Future<Output = Result<bool>> + use<'a, 'ctx> {
+    let existing_setup: Vec<_> = guild_row
+        .setup_channels()
+        .filter(|id| guild.channels.contains_key(id))
+        .collect();
+
+    async move { channel_check_with(ctx, author_vc, guild_row, &existing_setup).await }
+}
+
+/// [`channel_check`] once it is known which setup channels still exist.
+async fn channel_check_with(
+    ctx: &impl CheckResponder,
+    author_vc: Option<serenity::ChannelId>,
+    guild_row: &GuildRow,
+    existing_setup: &[serenity::ChannelId],
+) -> Result<bool> {
+    let channel_id = ctx.channel_id();
+    if guild_row.is_setup_channel(channel_id) || author_vc == Some(channel_id) {
+        return Ok(true);
+    }
+
+    let msg = if !existing_setup.is_empty() {
+        let channels = existing_setup.iter().map(|id| id.mention()).join(", ");
+        ctx.translate("You ran this command in the wrong channel, please move to {channels}.")
+            .replace("{channels}", &channels)
+    } else if guild_row.setup_channels().next().is_none() {
+        String::from(ctx.translate("You haven't setup the bot, please run /setup!"))
+    } else {
+        String::from(ctx.translate("Your setup channel was deleted, please run /setup again!"))
+    };
+
+    ctx.respond_error(msg).await?;
+    Ok(false)
+}
+
+/// Checks the author has one of the guild's `control_roles`, if any are set.
+async fn control_check(ctx: &Context<'_>, guild_row: &GuildRow) -> Result<bool> {
+    if guild_row.control_roles.is_empty() {
+        return Ok(true);
+    }
+
+    let member = ctx.author_member().await.try_unwrap()?;
+    if member
+        .roles
+        .iter()
+        .any(|role_id| guild_row.control_roles.contains(role_id))
+    {
+        return Ok(true);
+    }
+
+    // Administrators bypass the allowlist, so a server cannot lock itself out.
+    if ctx.author_permissions().await?.administrator() {
+        return Ok(true);
+    }
+
+    let msg = ctx.gettext("You don't have permission to control the bot");
+    ctx.send_error(msg.to_owned()).await?;
     Ok(false)
 }
 
@@ -55,11 +181,149 @@ async fn channel_check(ctx: &Context<'_>, author_vc: Option<serenity::ChannelId>
     guild_only,
     prefix_command,
     slash_command,
+    // The voice connection is shared, so spamming join from multiple users still thrashes it.
+    guild_cooldown = 5,
+    required_bot_permissions = "SEND_MESSAGES | EMBED_LINKS"
+)]
+pub async fn join(
+    ctx: Context<'_>,
+    #[description = "Skip the confirmation embed, for busy channels"] quiet: Option<bool>,
+    #[description = "The channel to join instead of yours, needs Move Members or Manage Server"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<serenity::GuildChannel>,
+) -> CommandResult {
+    let mut target = None;
+    if let Some(channel) = channel {
+        let permissions = ctx.author_permissions().await?;
+        if permissions.move_members() || permissions.manage_guild() {
+            target = Some(channel.id);
+        }
+    }
+
+    join_author_vc(ctx, target, false, quiet.unwrap_or(false)).await
+}
+
+/// Moves TTS Bot to the voice channel you're in, even if it is in another one!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    // Shares the voice connection with `join`, so needs the same cooldown.
+    guild_cooldown = 5,
+    required_bot_permissions = "SEND_MESSAGES | EMBED_LINKS"
+)]
+pub async fn summon(ctx: Context<'_>) -> CommandResult {
+    join_author_vc(ctx, None, true, false).await
+}
+
+/// Moves TTS Bot to your voice channel, keeping the queue, or joins if not in one!
+#[poise::command(
+    rename = "move",
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    guild_cooldown = 5,
     required_bot_permissions = "SEND_MESSAGES | EMBED_LINKS"
 )]
-pub async fn join(ctx: Context<'_>) -> CommandResult {
+pub async fn move_(ctx: Context<'_>) -> CommandResult {
+    join_author_vc(ctx, None, true, false).await
+}
+
+/// The permissions needed to TTS in a voice channel which are missing from `bot_permissions`.
+fn missing_voice_permissions(bot_permissions: serenity::Permissions) -> serenity::Permissions {
+    (serenity::Permissions::VIEW_CHANNEL
+        | serenity::Permissions::CONNECT
+        | serenity::Permissions::SPEAK)
+        - bot_permissions
+}
+
+/// [`JoinVCError::MissingPermissions`] if `bot_permissions` are missing any needed to TTS.
+fn missing_permissions_error(
+    ctx: Context<'_>,
+    bot_member: &serenity::Member,
+    bot_permissions: serenity::Permissions,
+) -> Option<JoinVCError> {
+    let missing = missing_voice_permissions(bot_permissions);
+    if missing.is_empty() {
+        return None;
+    }
+
+    // The fix is different if the role is fine, but the channel has overwrites denying it.
+    let denied_by_overwrites = ctx
+        .guild()
+        .is_some_and(|g| g.member_permissions(bot_member).contains(missing));
+
+    Some(JoinVCError::MissingPermissions {
+        missing,
+        denied_by_overwrites,
+    })
+}
+
+/// Checks if TTS Bot has the permissions to join your voice channel, without joining!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES"
+)]
+pub async fn check_permissions(ctx: Context<'_>) -> CommandResult {
     let author_vc = require!(
         ctx.author_vc(),
+        ctx.send_error(
+            ctx.gettext("I cannot check your voice channel unless you are in one!")
+                .to_owned()
+        )
+        .await
+        .map(drop)
+    );
+
+    let channel = require!(author_vc.to_channel(ctx).await?.guild(), Ok(()));
+    let bot_id = ctx.cache().current_user().id;
+    let bot_permissions = match channel.permissions_for_user(ctx, bot_id) {
+        Ok(permissions) => permissions,
+        Err(err) => {
+            tracing::warn!("Failed to get permissions in {author_vc} during check: {err:?}");
+
+            let msg = ctx.gettext("I couldn't determine my permissions, please try again!");
+            ctx.send_error(msg.to_owned()).await?;
+            return Ok(());
+        }
+    };
+
+    let missing_permissions = missing_voice_permissions(bot_permissions);
+    let msg = if missing_permissions.is_empty() {
+        ctx.gettext("All good! I have every permission I need in <#{channel_id}>.")
+            .replace("{channel_id}", &author_vc.to_string())
+    } else {
+        ctx.gettext("I am missing these permissions in <#{channel_id}>: {missing_permissions}")
+            .replace("{channel_id}", &author_vc.to_string())
+            .replace(
+                "{missing_permissions}",
+                &missing_permissions.get_permission_names().join(", "),
+            )
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Joins the author's voice channel, moving from another channel if `move_if_elsewhere` is set.
+///
+/// If `target` is set, that channel is joined instead, the caller must have checked the author
+/// is allowed to pick it.
+///
+/// If `quiet` is set, the join is confirmed with a reaction or ephemeral message instead of an embed.
+async fn join_author_vc(
+    ctx: Context<'_>,
+    target: Option<serenity::ChannelId>,
+    move_if_elsewhere: bool,
+    quiet: bool,
+) -> CommandResult {
+    let target_vc = require!(
+        target.or_else(|| ctx.author_vc()),
         ctx.send_error(
             ctx.gettext("I cannot join your voice channel unless you are in one!")
                 .to_owned()
@@ -68,17 +332,50 @@ pub async fn join(ctx: Context<'_>) -> CommandResult {
         .map(drop)
     );
 
-    if !channel_check(&ctx, Some(author_vc)).await? {
+    let guild_id = ctx.guild_id().unwrap();
+    tracing::info!(
+        guild_id = %guild_id,
+        channel_id = %target_vc,
+        user_id = %ctx.author().id,
+        "Joining voice channel"
+    );
+
+    if !channel_check(&ctx, Some(target_vc)).await? {
+        ctx.data().record_outcome("join", "wrong_channel");
         return Ok(());
     }
 
-    let guild_id = ctx.guild_id().unwrap();
-    let (bot_id, bot_face) = {
-        let current_user = ctx.cache().current_user();
-        (current_user.id, current_user.face())
+    let guild_row = ctx.data().guilds_db.get(guild_id.into()).await?;
+    if !control_check(&ctx, &guild_row).await? {
+        ctx.data().record_outcome("join", "not_allowed");
+        return Ok(());
+    }
+
+    let afk_channel_id = ctx
+        .guild()
+        .and_then(|g| g.afk_metadata.as_ref().map(|afk| afk.afk_channel_id));
+
+    if afk_channel_id == Some(target_vc) {
+        let msg = ctx.gettext("I can't do TTS in the AFK channel!");
+        ctx.send_error(msg.to_owned()).await?;
+        return Ok(());
+    }
+
+    let bot_id = ctx.cache().current_user().id;
+    let (bot_member, member) = match tokio::try_join!(
+        guild_id.member(ctx, bot_id),
+        guild_id.member(ctx, ctx.author().id)
+    ) {
+        Ok(members) => members,
+        Err(err) => {
+            tracing::warn!("Failed to fetch members for join in {guild_id}: {err:?}");
+
+            let msg = ctx.gettext("I couldn't verify your membership, please try again!");
+            ctx.send_error(msg.to_owned()).await?;
+            return Ok(());
+        }
     };
 
-    let bot_member = guild_id.member(ctx, bot_id).await?;
     if let Some(communication_disabled_until) = bot_member.communication_disabled_until {
         if communication_disabled_until > serenity::Timestamp::now() {
             let msg = ctx.gettext("I am timed out, please ask a moderator to remove the timeout");
@@ -87,165 +384,1522 @@ pub async fn join(ctx: Context<'_>) -> CommandResult {
         }
     }
 
-    let author = ctx.author();
-    let member = guild_id.member(ctx, author.id).await?;
-    let channel = author_vc.to_channel(ctx).await?.guild().unwrap();
+    let channel = match target_vc.to_channel(ctx).await {
+        Ok(channel) => channel.guild(),
+        // The channel was deleted between reading the voice state and fetching it.
+        Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(err)))
+            if err.status_code == serenity::StatusCode::NOT_FOUND =>
+        {
+            tracing::debug!("Failed to fetch {target_vc} during join: {err:?}");
+            None
+        }
+        Err(err) => return Err(err.into()),
+    };
 
-    let missing_permissions = (serenity::Permissions::VIEW_CHANNEL
-        | serenity::Permissions::CONNECT
-        | serenity::Permissions::SPEAK)
-        - channel.permissions_for_user(ctx, bot_id)?;
+    let Some(channel) = channel else {
+        return handle_join_error(ctx, "join", target_vc, JoinVCError::ChannelDeleted).await;
+    };
 
-    if !missing_permissions.is_empty() {
-        let msg = ctx.gettext("I do not have permission to TTS in your voice channel, please ask a server administrator to give me: {missing_permissions}")
-            .replace("{missing_permissions}", &missing_permissions.get_permission_names().join(", "));
+    // Needs the guild to be cached, which can briefly not be the case after a reconnect.
+    let bot_permissions = match channel.permissions_for_user(ctx, bot_id) {
+        Ok(permissions) => permissions,
+        Err(err) => {
+            tracing::warn!("Failed to get permissions in {target_vc} during join: {err:?}");
 
-        ctx.send_error(msg).await?;
-        return Ok(());
+            let msg = ctx.gettext("I couldn't determine my permissions, please try again!");
+            ctx.send_error(msg.to_owned()).await?;
+            return Ok(());
+        }
+    };
+    if let Some(err) = missing_permissions_error(ctx, &bot_member, bot_permissions) {
+        return handle_join_error(ctx, "join", target_vc, err).await;
+    }
+
+    // Discord never responds to joining a full channel, so this has to be checked before joining.
+    if let Some(user_limit) = channel
+        .user_limit
+        .map(u32::from)
+        .filter(|&limit| limit != 0)
+        && !bot_permissions.move_members()
+        && channel.members(ctx.cache())?.len() >= user_limit as usize
+    {
+        return handle_join_error(ctx, "join", target_vc, JoinVCError::ChannelFull).await;
     }
 
     let data = ctx.data();
-    if let Some(bot_vc) = data.songbird.get(guild_id) {
-        let bot_channel_id = bot_vc.lock().await.current_channel();
-        if let Some(bot_channel_id) = bot_channel_id {
-            if bot_channel_id.0.get() == author_vc.get() {
-                ctx.say(ctx.gettext("I am already in your voice channel!"))
-                    .await?;
-                return Ok(());
-            };
+    let mut is_move = false;
+    if let Some(bot_channel_id) = data.connected_channel(guild_id) {
+        if bot_channel_id == target_vc {
+            ctx.say(ctx.gettext("I am already in your voice channel!"))
+                .await?;
+            return Ok(());
+        };
 
+        if !move_if_elsewhere {
             ctx.say(
                 ctx.gettext("I am already in <#{channel_id}>!")
-                    .replace("{channel_id}", &bot_channel_id.0.to_string()),
+                    .replace("{channel_id}", &bot_channel_id.to_string()),
             )
             .await?;
             return Ok(());
         }
+
+        is_move = true;
     };
 
     {
         let _typing = ctx.defer_or_broadcast().await?;
 
         let join_vc_lock = JoinVCToken::acquire(data, guild_id);
-        let join_vc_result = data
-            .songbird
-            .join_vc(join_vc_lock.lock().await, author_vc)
-            .await;
+        let join_vc_token = join_vc_lock.lock().await;
+
+        // Another join may have finished while waiting for the token.
+        if let Some(call_lock) = data.songbird.get(guild_id)
+            && let Some(bot_channel_id) = call_lock.lock().await.current_channel()
+            && bot_channel_id.0.get() == target_vc.get()
+        {
+            ctx.say(ctx.gettext("I am already in your voice channel!"))
+                .await?;
+            return Ok(());
+        }
+
+        // Fetching members and waiting for the token takes time, the author may have left since.
+        if target.is_none() && ctx.author_vc() != Some(target_vc) {
+            data.record_outcome("join", "author_left");
+            let msg = ctx.gettext("You left your voice channel before I could join!");
+            ctx.send_error(msg.to_owned()).await?;
+            return Ok(());
+        }
+
+        let join_vc_result = data.songbird.join_vc(join_vc_token, target_vc).await;
 
         if let Err(err) = join_vc_result {
-            return if let JoinError::TimedOut = err {
-                let msg = ctx.gettext("I failed to join your voice channel, please check I have the right permissions and try again!");
-                ctx.send_error(msg.to_owned()).await?;
-                Ok(())
-            } else {
-                Err(err.into())
-            };
+            return handle_join_error(ctx, "join", target_vc, err).await;
         };
+
+        // Set now, so commands straight after the join do not have to wait for the voice state.
+        data.connected_channels.insert(guild_id, target_vc);
     }
 
-    ctx.send(
-        poise::CreateReply::default().embed(
-            serenity::CreateEmbed::default()
-                .title(ctx.gettext("Joined your voice channel!"))
-                .description(ctx.gettext("Just type normally and TTS Bot will say your messages!"))
-                .thumbnail(bot_face)
-                .author(CreateEmbedAuthor::new(member.display_name()).icon_url(author.face()))
-                .footer(CreateEmbedFooter::new(random_footer(
-                    &data.config.main_server_invite,
-                    bot_id,
-                    ctx.current_catalog(),
-                ))),
-        ),
+    tracing::info!(
+        guild_id = %guild_id,
+        channel_id = %target_vc,
+        user_id = %ctx.author().id,
+        outcome = "success",
+        "Joined voice channel"
+    );
+    data.record_outcome("join", "success");
+    post_voice_log(
+        ctx,
+        &guild_row,
+        ctx.gettext("Joined voice channel"),
+        target_vc,
     )
-    .await?;
-    Ok(())
-}
+    .await;
 
-/// Leaves voice channel TTS Bot is in!
-#[poise::command(
-    category = "Main Commands",
-    guild_only,
-    prefix_command,
-    slash_command,
-    required_bot_permissions = "SEND_MESSAGES"
-)]
-pub async fn leave(ctx: Context<'_>) -> CommandResult {
-    let (guild_id, author_vc) = {
-        let guild = require_guild!(ctx);
-        let channel_id = guild
-            .voice_states
-            .get(&ctx.author().id)
-            .and_then(|vs| vs.channel_id);
+    // Bots join stages as audience, so would not be heard without becoming a speaker.
+    if channel.kind == serenity::ChannelType::Stage {
+        become_stage_speaker(ctx, &channel, bot_permissions).await?;
+    }
 
-        (guild.id, channel_id)
-    };
+    // The existing call was moved, so the queue survived and the instructions were already sent.
+    if is_move {
+        let msg = ctx
+            .gettext("Moved to <#{channel_id}>!")
+            .replace("{channel_id}", &target_vc.to_string());
 
-    let data = ctx.data();
-    let bot_vc = {
-        if let Some(handler) = data.songbird.get(guild_id) {
-            handler.lock().await.current_channel()
-        } else {
-            None
-        }
-    };
+        ctx.say(msg).await?;
+        return Ok(());
+    }
 
-    if let Some(bot_vc) = bot_vc {
-        if !channel_check(&ctx, author_vc).await? {
-        } else if author_vc.map_or(true, |author_vc| bot_vc.0.get() != author_vc.get()) {
-            ctx.say(ctx.gettext(
-                "Error: You need to be in the same voice channel as me to make me leave!",
-            ))
-            .await?;
-        } else {
-            data.songbird.remove(guild_id).await?;
-            data.last_to_xsaid_tracker.remove(&guild_id);
+    let title = ctx.gettext("Joined your voice channel!");
+    let description = ctx.gettext("Just type normally and TTS Bot will say your messages!");
 
-            ctx.say(ctx.gettext("Left voice channel!")).await?;
+    if quiet {
+        match ctx {
+            poise::Context::Prefix(_) => confirm(ctx).await?,
+            poise::Context::Application(_) => {
+                ctx.send(poise::CreateReply::default().content(title).ephemeral(true))
+                    .await?;
+            }
         }
     } else {
-        ctx.say(ctx.gettext("Error: How do I leave a voice channel if I am not in one?"))
-            .await?;
+        let mut embed = instructions_embed(ctx, &member, title, description);
+
+        // Latency is only known after the first heartbeat, so may be missing on a fresh shard.
+        if let Some(latency) = shard_latency(ctx).await {
+            embed = embed.field(
+                ctx.gettext("Latency"),
+                format!("{}ms", latency.as_millis()),
+                true,
+            );
+        }
+
+        // Normally empty, but a queue can survive a reconnect.
+        let queued = match data.songbird.get(guild_id) {
+            Some(call_lock) => call_lock.lock().await.queue().len(),
+            None => 0,
+        };
+
+        if queued != 0 {
+            embed = embed.field(ctx.gettext("Queued Messages"), queued.to_string(), true);
+        }
+
+        // EMBED_LINKS is checked before the command runs, but can be revoked while joining.
+        match ctx.send(poise::CreateReply::default().embed(embed)).await {
+            Err(serenity::Error::Http(error))
+                if error.status_code() == Some(serenity::StatusCode::FORBIDDEN) =>
+            {
+                tracing::warn!("Missing permissions to send join embed in {guild_id}: {error:?}");
+                ctx.say(title).await?;
+            }
+            result => drop(result?),
+        }
     }
 
+    // The voice state is only cached once the join completes, but server mutes are on the member.
+    let is_muted = ctx
+        .guild()
+        .and_then(|g| {
+            g.voice_states
+                .get(&bot_id)
+                .map(|vs| vs.mute || vs.self_mute)
+        })
+        .unwrap_or(bot_member.mute);
+
+    if is_muted {
+        let muted_title = ctx.gettext("I am muted!");
+        let muted_description = ctx.gettext(
+            "I have been muted in this server, so nobody will hear me! Please ask a moderator to unmute me.",
+        );
+
+        let embed = serenity::CreateEmbed::default()
+            .colour(constants::RED)
+            .title(muted_title)
+            .description(muted_description);
+
+        // Falls back to plain text the same way as the join embed, so the warning is never lost.
+        match ctx.send(poise::CreateReply::default().embed(embed)).await {
+            Err(serenity::Error::Http(error))
+                if error.status_code() == Some(serenity::StatusCode::FORBIDDEN) =>
+            {
+                ctx.say(format!("**{muted_title}** {muted_description}"))
+                    .await?;
+            }
+            result => drop(result?),
+        }
+    }
+
+    ctx.speak_response(&format!("{title} {description}"))
+        .await?;
     Ok(())
 }
 
-/// Clears the message queue!
+/// Tells the user why joining failed, bubbling up errors that are not their fault.
+///
+/// The outcome is recorded under `command`, as reconnecting can fail the same ways as joining.
+async fn handle_join_error(
+    ctx: Context<'_>,
+    command: &'static str,
+    channel_id: serenity::ChannelId,
+    err: JoinVCError,
+) -> CommandResult {
+    let outcome = join_error_outcome(&err);
+
+    tracing::info!(
+        guild_id = %ctx.guild_id().unwrap(),
+        channel_id = %channel_id,
+        user_id = %ctx.author().id,
+        outcome,
+        "Failed to join voice channel"
+    );
+    ctx.data().record_outcome(command, outcome);
+
+    let Some(msg) = join_error_message(&ctx, &err) else {
+        return Err(err.into());
+    };
+
+    ctx.send_error(msg).await?;
+    Ok(())
+}
+
+/// The outcome recorded for a failed join.
+fn join_error_outcome(err: &JoinVCError) -> &'static str {
+    match err {
+        JoinVCError::Timeout => "timeout",
+        JoinVCError::Dropped => "dropped",
+        JoinVCError::MissingPermissions { .. } => "missing_permissions",
+        JoinVCError::ChannelFull => "channel_full",
+        JoinVCError::ChannelDeleted => "channel_deleted",
+        JoinVCError::Other(_) => "error",
+    }
+}
+
+/// What to tell the user about a failed join, or `None` if it is not their fault.
+fn join_error_message(ctx: &impl CheckResponder, err: &JoinVCError) -> Option<String> {
+    Some(match err {
+        JoinVCError::Timeout => String::from(
+            ctx.translate("I timed out joining your voice channel, please try again!"),
+        ),
+        JoinVCError::Dropped => String::from(
+            ctx.translate("I lost connection while joining your voice channel, please try again!"),
+        ),
+        JoinVCError::MissingPermissions {
+            missing,
+            denied_by_overwrites: true,
+        } => ctx.translate("My role has permission to TTS, but your voice channel's permission overrides deny me: {missing_permissions}. Please ask a server administrator to allow these for me in the channel's settings!")
+            .replace("{missing_permissions}", &missing.get_permission_names().join(", ")),
+        JoinVCError::MissingPermissions {
+            missing,
+            denied_by_overwrites: false,
+        } => ctx.translate("I do not have permission to TTS in your voice channel, please ask a server administrator to give my role: {missing_permissions}")
+            .replace("{missing_permissions}", &missing.get_permission_names().join(", ")),
+        JoinVCError::ChannelFull => String::from(ctx.translate("I cannot join your voice channel as it is full, please ask a server administrator to raise the user limit or give me Move Members!")),
+        JoinVCError::ChannelDeleted => String::from(
+            ctx.translate("Your voice channel no longer exists, please join another and try again!"),
+        ),
+        JoinVCError::Other(_) => return None,
+    })
+}
+
+/// Builds the embed explaining how to use TTS Bot, shared by `join` and `howto`.
+fn instructions_embed(
+    ctx: Context<'_>,
+    member: &serenity::Member,
+    title: &str,
+    description: &str,
+) -> serenity::CreateEmbed {
+    let (bot_id, bot_face) = {
+        let current_user = ctx.cache().current_user();
+        (current_user.id, current_user.face())
+    };
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .thumbnail(bot_face)
+        .author(
+            CreateEmbedAuthor::new(funcs::embed_author_name(member)).icon_url(member.user.face()),
+        )
+        .footer(CreateEmbedFooter::new(random_footer(
+            &ctx.data().config.main_server_invite,
+            bot_id,
+            ctx.current_catalog(),
+        )))
+}
+
+/// Shows how to use TTS Bot, for anyone who missed it when the bot joined!
 #[poise::command(
-    aliases("skip"),
     category = "Main Commands",
     guild_only,
     prefix_command,
     slash_command,
-    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS"
+    required_bot_permissions = "SEND_MESSAGES | EMBED_LINKS"
 )]
-pub async fn clear(ctx: Context<'_>) -> CommandResult {
+pub async fn howto(ctx: Context<'_>) -> CommandResult {
     if !channel_check(&ctx, ctx.author_vc()).await? {
         return Ok(());
     }
 
-    let guild_id = ctx.guild_id().unwrap();
-    if let Some(call_lock) = ctx.data().songbird.get(guild_id) {
-        call_lock.lock().await.queue().stop();
-
-        match ctx {
-            poise::Context::Prefix(ctx) => {
-                // Prefixed command, just add a thumbsup reaction
-                ctx.msg.react(ctx.serenity_context(), '👍').await?;
-            }
-            poise::Context::Application(_) => {
-                // Slash command, no message to react to, just say thumbsup
-                ctx.say('👍').await?;
-            }
-        }
-    } else {
-        ctx.say(ctx.gettext("**Error**: I am not in a voice channel!"))
-            .await?;
-    };
+    let member = ctx.author_member().await.try_unwrap()?;
+    let title = ctx.gettext("How to use TTS Bot");
+    let description = ctx.gettext("Just type normally and TTS Bot will say your messages!");
 
+    let embed = instructions_embed(ctx, &member, title, description);
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
     Ok(())
 }
 
-pub fn commands() -> [Command; 3] {
-    [join(), leave(), clear()]
+/// Gets the gateway latency of the shard the command was run on.
+async fn shard_latency(ctx: Context<'_>) -> Option<Duration> {
+    let shard_id = ctx.serenity_context().shard_id;
+    let runners = ctx.framework().shard_manager().runners.lock().await;
+    runners.get(&shard_id).and_then(|runner| runner.latency)
+}
+
+/// Checks if the bot can send messages in the current channel, telling the author in DMs if not.
+async fn check_can_respond(ctx: Context<'_>) -> Result<bool> {
+    let bot_id = ctx.cache().current_user().id;
+    let channel = require!(ctx.channel_id().to_channel(ctx).await?.guild(), Ok(false));
+
+    let missing_permissions =
+        serenity::Permissions::SEND_MESSAGES - channel.permissions_for_user(ctx, bot_id)?;
+
+    if missing_permissions.is_empty() {
+        return Ok(true);
+    }
+
+    tracing::warn!(
+        "Missing {missing_permissions:?} in channel {} for {} command in guild {}",
+        channel.id,
+        ctx.command().qualified_name,
+        channel.guild_id
+    );
+
+    let msg = ctx.gettext("I cannot respond in <#{channel_id}>, please ask a server administrator to give me: {missing_permissions}")
+        .replace("{channel_id}", &channel.id.to_string())
+        .replace("{missing_permissions}", &missing_permissions.get_permission_names().join(", "));
+
+    match ctx
+        .author()
+        .direct_message(ctx, CreateMessage::default().content(msg))
+        .await
+    {
+        Err(serenity::Error::Http(error))
+            if error.status_code() == Some(serenity::StatusCode::FORBIDDEN) => {}
+        Err(error) => return Err(error.into()),
+        Ok(_) => {}
+    }
+
+    Ok(false)
+}
+
+/// Posts a record of joining or leaving `channel_id` to the guild's log channel, if one is set.
+///
+/// Failures, such as missing permissions in the log channel, are only logged, as the
+/// join or leave itself has already succeeded.
+async fn post_voice_log(
+    ctx: Context<'_>,
+    guild_row: &GuildRow,
+    title: &str,
+    channel_id: serenity::ChannelId,
+) {
+    let Some(log_channel) = guild_row.log_channel else {
+        return;
+    };
+
+    let embed = CreateEmbed::default()
+        .title(title)
+        .colour(ctx.neutral_colour().await)
+        .field(
+            ctx.gettext("User"),
+            ctx.author().mention().to_string(),
+            true,
+        )
+        .field(
+            ctx.gettext("Channel"),
+            channel_id.mention().to_string(),
+            true,
+        )
+        .timestamp(serenity::Timestamp::now());
+
+    let message = CreateMessage::default().embed(embed);
+    if let Err(err) = log_channel.send_message(ctx.http(), message).await {
+        tracing::warn!(
+            "Failed to post to log channel {log_channel} in {}: {err:?}",
+            ctx.guild_id().unwrap()
+        );
+    }
+}
+
+/// Moves the bot to the speakers of a stage channel, or requests to speak if it cannot do so itself.
+async fn become_stage_speaker(
+    ctx: Context<'_>,
+    channel: &serenity::GuildChannel,
+    bot_permissions: serenity::Permissions,
+) -> Result<()> {
+    let can_unsuppress = bot_permissions.mute_members();
+    let builder = if can_unsuppress {
+        EditVoiceState::new().suppress(false)
+    } else {
+        EditVoiceState::new().request_to_speak(true)
+    };
+
+    if let Err(err) = channel.edit_own_voice_state(ctx.http(), builder).await {
+        tracing::warn!(
+            "Failed to become a speaker in stage {}: {err:?}",
+            channel.id
+        );
+
+        let msg = ctx.gettext("I joined the stage, but couldn't become a speaker! Please give me the `Mute Members` permission, or invite me to speak.");
+        ctx.send_error(msg.to_owned()).await?;
+        return Ok(());
+    }
+
+    if !can_unsuppress {
+        let msg =
+            ctx.gettext("I have requested to speak, please accept my request so I can be heard!");
+        ctx.say(msg).await?;
+    }
+
+    Ok(())
+}
+
+/// Leaves voice channel TTS Bot is in!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    aliases("disconnect", "dc")
+)]
+pub async fn leave(ctx: Context<'_>) -> CommandResult {
+    // Slash commands can always be responded to, so only prefix commands need checking.
+    if let poise::Context::Prefix(_) = ctx
+        && !check_can_respond(ctx).await?
+    {
+        return Ok(());
+    }
+
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    let guild_row = data.guilds_db.get(guild_id.into()).await?;
+    tracing::info!(
+        guild_id = %guild_id,
+        channel_id = %ctx.channel_id(),
+        user_id = %ctx.author().id,
+        "Leaving voice channel"
+    );
+
+    // Both the author's voice channel and the setup channels are read from one cache lookup.
+    let (author_vc, check) = match ctx.guild() {
+        Some(guild) => {
+            let author_vc = guild
+                .voice_states
+                .get(&ctx.author().id)
+                .and_then(|vs| vs.channel_id);
+
+            let check = channel_check_in(&ctx, &guild, author_vc, &guild_row);
+            (author_vc, Some(check))
+        }
+        None => (None, None),
+    };
+
+    if let Some(bot_vc) = data.connected_channel(guild_id) {
+        let passed_check = match check {
+            Some(check) => check.await?,
+            // Falls back to HTTP for the setup channels if the guild is not cached yet.
+            None => channel_check(&ctx, author_vc).await?,
+        };
+
+        if !passed_check {
+            data.record_outcome("leave", "wrong_channel");
+        } else if !control_check(&ctx, &guild_row).await? {
+            data.record_outcome("leave", "not_allowed");
+        } else if author_vc != Some(bot_vc) {
+            data.record_outcome("leave", "wrong_voice_channel");
+            ctx.say(ctx.gettext(
+                "Error: You need to be in the same voice channel as me to make me leave!",
+            ))
+            .await?;
+        } else {
+            {
+                // Leaving can be slow, the guard is dropped before confirming or on error.
+                let _typing = ctx.defer_or_broadcast().await?;
+                data.songbird.remove(guild_id).await?;
+            }
+
+            data.last_to_xsaid_tracker.remove(&guild_id);
+            data.connected_channels.remove(&guild_id);
+            data.last_spoken.remove(&guild_id);
+
+            tracing::info!(
+                guild_id = %guild_id,
+                channel_id = %bot_vc,
+                user_id = %ctx.author().id,
+                outcome = "success",
+                "Left voice channel"
+            );
+            data.record_outcome("leave", "success");
+            post_voice_log(ctx, &guild_row, ctx.gettext("Left voice channel"), bot_vc).await;
+
+            ctx.say(ctx.gettext("Left voice channel!")).await?;
+        }
+    } else if data.songbird.get(guild_id).is_some()
+        || data.last_to_xsaid_tracker.contains_key(&guild_id)
+    {
+        // Discord disconnected the bot, such as being kicked from the channel, but the state was left.
+        drop(data.songbird.remove(guild_id).await);
+        data.last_to_xsaid_tracker.remove(&guild_id);
+        data.last_spoken.remove(&guild_id);
+
+        tracing::info!(
+            guild_id = %guild_id,
+            user_id = %ctx.author().id,
+            outcome = "stale",
+            "Cleaned up stale voice state"
+        );
+        data.record_outcome("leave", "stale");
+
+        ctx.say(ctx.gettext("Cleaned up stale voice state."))
+            .await?;
+    } else {
+        data.record_outcome("leave", "not_connected");
+        ctx.send_not_connected().await?;
+    }
+
+    Ok(())
+}
+
+/// Reconnects TTS Bot to its voice channel, keeping the queue, if it has stopped speaking!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES"
+)]
+pub async fn reconnect(ctx: Context<'_>) -> CommandResult {
+    if !channel_check(&ctx, ctx.author_vc()).await? {
+        return Ok(());
+    }
+
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    let guild_row = data.guilds_db.get(guild_id.into()).await?;
+    if !control_check(&ctx, &guild_row).await? {
+        return Ok(());
+    }
+
+    let Some(bot_vc) = data.connected_channel(guild_id) else {
+        data.record_outcome("reconnect", "not_connected");
+        ctx.send_not_connected().await?;
+        return Ok(());
+    };
+
+    let bot_id = ctx.cache().current_user().id;
+    let bot_member = guild_id.member(ctx, bot_id).await?;
+    let channel = match bot_vc.to_channel(ctx).await {
+        Ok(channel) => channel.guild(),
+        // The channel was deleted, but Discord has not disconnected the bot yet.
+        Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(err)))
+            if err.status_code == serenity::StatusCode::NOT_FOUND =>
+        {
+            tracing::debug!("Failed to fetch {bot_vc} during reconnect: {err:?}");
+            None
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let Some(channel) = channel else {
+        data.record_outcome("reconnect", "channel_deleted");
+        let msg = ctx.gettext("My voice channel no longer exists, please use /join instead!");
+        ctx.send_error(msg.to_owned()).await?;
+        return Ok(());
+    };
+
+    // Needs the guild to be cached, which can briefly not be the case after a reconnect.
+    let bot_permissions = match channel.permissions_for_user(ctx, bot_id) {
+        Ok(permissions) => permissions,
+        Err(err) => {
+            tracing::warn!("Failed to get permissions in {bot_vc} during reconnect: {err:?}");
+            data.record_outcome("reconnect", "error");
+
+            let msg = ctx.gettext("I couldn't determine my permissions, please try again!");
+            ctx.send_error(msg.to_owned()).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(err) = missing_permissions_error(ctx, &bot_member, bot_permissions) {
+        return handle_join_error(ctx, "reconnect", bot_vc, err).await;
+    }
+
+    let messages = {
+        let _typing = ctx.defer_or_broadcast().await?;
+
+        let join_vc_lock = JoinVCToken::acquire(data, guild_id);
+        let join_vc_token = join_vc_lock.lock().await;
+
+        // The audio already sent to the old connection is gone, so has to be fetched again.
+        let messages = match data.songbird.get(guild_id) {
+            Some(call_lock) => {
+                let call = call_lock.lock().await;
+                let messages = funcs::queued_messages(call.queue());
+                call.queue().stop();
+                messages
+            }
+            None => Vec::new(),
+        };
+
+        drop(data.songbird.remove(guild_id).await);
+        if let Err(err) = data.songbird.join_vc(join_vc_token, bot_vc).await {
+            data.connected_channels.remove(&guild_id);
+            return handle_join_error(ctx, "reconnect", bot_vc, err).await;
+        }
+
+        data.connected_channels.insert(guild_id, bot_vc);
+        messages
+    };
+
+    let call_lock = data.songbird.get(guild_id).try_unwrap()?;
+    let clear_generation = data.clear_generation(guild_id);
+    let mut requeued = 0;
+    for message in messages {
+        let url = message.url.clone();
+        let author_id = message.author_id;
+        if funcs::enqueue_audio(data, &call_lock, guild_id, author_id, url, clear_generation)
+            .await?
+            .is_some()
+        {
+            requeued += 1;
+        }
+    }
+
+    tracing::info!(
+        guild_id = %guild_id,
+        channel_id = %bot_vc,
+        user_id = %ctx.author().id,
+        requeued,
+        "Reconnected to voice channel"
+    );
+    data.record_outcome("reconnect", "success");
+
+    let msg = if requeued == 0 {
+        ctx.gettext("Reconnected to <#{channel_id}>!")
+            .replace("{channel_id}", &bot_vc.to_string())
+    } else {
+        ctx.gettext("Reconnected to <#{channel_id}>, keeping {count} queued messages!")
+            .replace("{channel_id}", &bot_vc.to_string())
+            .replace("{count}", &requeued.to_string())
+    };
+
+    ctx.say(msg).await?;
+
+    // The text response mentions the channel, which would be read out as an ID.
+    ctx.speak_response(ctx.gettext("Reconnected!")).await?;
+    Ok(())
+}
+
+/// Makes TTS Bot leave its voice channel, even if you are not in it!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_GUILD",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("forceleave", "force_disconnect")
+)]
+pub async fn force_leave(ctx: Context<'_>) -> CommandResult {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+
+    let bot_id = ctx.cache().current_user().id;
+    let cached_vc = ctx
+        .guild()
+        .and_then(|guild| guild.voice_states.get(&bot_id)?.channel_id);
+
+    let bot_vc = data
+        .connected_channels
+        .remove(&guild_id)
+        .map(|(_, channel_id)| channel_id)
+        .or(cached_vc);
+
+    let has_call = data.songbird.get(guild_id).is_some();
+    if has_call || bot_vc.is_some() {
+        if !has_call {
+            // Discord still has the bot connected, but the call is gone. A new call has no
+            // channel, so leaving with it sends the voice state update disconnecting the bot.
+            data.songbird.get_or_insert(guild_id);
+        }
+
+        data.songbird.remove(guild_id).await?;
+        data.last_to_xsaid_tracker.remove(&guild_id);
+        data.last_spoken.remove(&guild_id);
+
+        let outcome = if has_call { "success" } else { "stale" };
+        tracing::info!(
+            guild_id = %guild_id,
+            channel_id = ?bot_vc,
+            user_id = %ctx.author().id,
+            outcome,
+            "Force left voice channel"
+        );
+        data.record_outcome("force_leave", outcome);
+
+        ctx.say(ctx.gettext("Left voice channel!")).await?;
+    } else {
+        data.record_outcome("force_leave", "not_connected");
+        ctx.send_not_connected().await?;
+    }
+
+    Ok(())
+}
+
+/// Clears the message queue!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS"
+)]
+pub async fn clear(ctx: Context<'_>) -> CommandResult {
+    if !channel_check(&ctx, ctx.author_vc()).await? {
+        ctx.data().record_outcome("clear", "wrong_channel");
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    if let Some(call_lock) = ctx.data().songbird.get(guild_id) {
+        let queue_len = call_lock.lock().await.queue().len();
+        if queue_len > CLEAR_CONFIRM_THRESHOLD {
+            let prompt = ctx
+                .gettext("Are you sure you want to clear {count} queued messages?")
+                .replace("{count}", &queue_len.to_string());
+
+            let confirmed = funcs::confirm_dialog(
+                ctx,
+                &prompt,
+                ctx.gettext("Yes, clear the queue").to_owned(),
+                ctx.gettext("No, keep the queue").to_owned(),
+            )
+            .await?;
+
+            match confirmed {
+                Some(true) => {}
+                Some(false) => {
+                    ctx.say(ctx.gettext("Cancelled!")).await?;
+                    return Ok(());
+                }
+                // Timed out, so leave the queue alone.
+                None => return Ok(()),
+            }
+        }
+
+        let cleared_count = {
+            let call = call_lock.lock().await;
+            ctx.data().clear_queue(guild_id, &call)
+        };
+
+        let outcome = if cleared_count == 0 {
+            "empty"
+        } else {
+            "success"
+        };
+        ctx.data().record_outcome("clear", outcome);
+
+        if cleared_count == 0 {
+            ctx.say(ctx.gettext("The queue was already empty!")).await?;
+        } else {
+            let msg = ctx
+                .gettext("Cleared {count} queued messages!")
+                .replace("{count}", &cleared_count.to_string());
+
+            match ctx {
+                poise::Context::Prefix(_) => confirm(ctx).await?,
+                poise::Context::Application(_) => {
+                    ctx.say(msg.clone()).await?;
+                }
+            }
+
+            ctx.speak_response(&msg).await?;
+        }
+    } else {
+        ctx.data().record_outcome("clear", "not_connected");
+        ctx.send_not_connected().await?;
+    };
+
+    Ok(())
+}
+
+/// Removes the queued messages of a single user, such as someone spamming!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_MESSAGES",
+    required_bot_permissions = "SEND_MESSAGES"
+)]
+pub async fn clear_user(
+    ctx: Context<'_>,
+    #[description = "The user to remove the queued messages of"] user: serenity::User,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(call_lock) = ctx.data().songbird.get(guild_id) else {
+        ctx.send_not_connected().await?;
+        return Ok(());
+    };
+
+    let removed_count = {
+        let call = call_lock.lock().await;
+        funcs::remove_queued_from(call.queue(), user.id)
+    };
+
+    let msg = if removed_count == 0 {
+        ctx.gettext("{user} has no queued messages!")
+            .replace("{user}", &user.mention().to_string())
+    } else {
+        ctx.gettext("Removed {count} queued messages from {user}!")
+            .replace("{count}", &removed_count.to_string())
+            .replace("{user}", &user.mention().to_string())
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Shows how many people are in the voice channel with TTS Bot!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("vccount", "voice_count")
+)]
+pub async fn voicecount(ctx: Context<'_>) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(bot_channel_id) = ctx.data().connected_channel(guild_id) else {
+        ctx.send_not_connected().await?;
+        return Ok(());
+    };
+
+    let bot_id = ctx.cache().current_user().id;
+    let count = {
+        let guild = require_guild!(ctx);
+        guild
+            .voice_states
+            .values()
+            .filter(|vs| vs.channel_id == Some(bot_channel_id) && vs.user_id != bot_id)
+            .filter(|vs| {
+                // Uncached members are counted as people, the same as the voice sweeper.
+                let member = vs
+                    .member
+                    .as_ref()
+                    .or_else(|| guild.members.get(&vs.user_id));
+                member.map_or(true, |member| !member.user.bot)
+            })
+            .count()
+    };
+
+    let msg = ctx
+        .gettext("There are {count} people in <#{channel_id}> with me!")
+        .replace("{count}", &count.to_string())
+        .replace("{channel_id}", &bot_channel_id.to_string());
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Shows the messages waiting to be read out!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | EMBED_LINKS"
+)]
+pub async fn queue(ctx: Context<'_>) -> CommandResult {
+    const MAX_SHOWN: usize = 10;
+    const MAX_TEXT_CHARS: usize = 80;
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(call_lock) = ctx.data().songbird.get(guild_id) else {
+        ctx.send_not_connected().await?;
+        return Ok(());
+    };
+
+    let messages = funcs::queued_messages(call_lock.lock().await.queue());
+    if messages.is_empty() {
+        ctx.say(ctx.gettext("Nothing queued!")).await?;
+        return Ok(());
+    }
+
+    let mut description = String::new();
+    for (index, message) in messages.iter().take(MAX_SHOWN).enumerate() {
+        let mut text = message.text().map(Cow::into_owned).unwrap_or_default();
+        if funcs::truncate_chars(&mut text, MAX_TEXT_CHARS) {
+            text.push('…');
+        }
+
+        // Index 0 is the message currently being read out.
+        let position = if index == 0 {
+            Cow::Borrowed("▶")
+        } else {
+            Cow::Owned(format!("{index}."))
+        };
+
+        writeln!(
+            description,
+            "{position} {}: {}",
+            message.author_id.mention(),
+            text
+        )?;
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title(ctx.gettext("Queued Messages"))
+        .description(description)
+        .colour(ctx.neutral_colour().await);
+
+    if messages.len() > MAX_SHOWN {
+        let footer = ctx
+            .gettext("And {count} more messages")
+            .replace("{count}", &(messages.len() - MAX_SHOWN).to_string());
+
+        embed = embed.footer(CreateEmbedFooter::new(footer));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Shows which voice channel TTS Bot is in, and if it is reading anything!
+#[poise::command(
+    rename = "where",
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("status")
+)]
+pub async fn where_(ctx: Context<'_>) -> CommandResult {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(bot_channel_id) = data.connected_channel(guild_id) else {
+        ctx.send_not_connected().await?;
+        return Ok(());
+    };
+
+    // Only the queue length needs the call, the channel is tracked without locking it.
+    let queue_len = match data.songbird.get(guild_id) {
+        Some(call_lock) => call_lock.lock().await.queue().len(),
+        None => 0,
+    };
+
+    let msg = if queue_len == 0 {
+        ctx.gettext("I am in <#{channel_id}>, with nothing queued.")
+            .replace("{channel_id}", &bot_channel_id.to_string())
+    } else {
+        ctx.gettext("I am in <#{channel_id}>, with {count} queued messages.")
+            .replace("{channel_id}", &bot_channel_id.to_string())
+            .replace("{count}", &queue_len.to_string())
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Pauses the message currently being read out!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS"
+)]
+pub async fn pause(ctx: Context<'_>) -> CommandResult {
+    control_queue(ctx, TrackQueue::pause).await?;
+    Ok(())
+}
+
+/// Resumes the message currently being read out!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS"
+)]
+pub async fn resume(ctx: Context<'_>) -> CommandResult {
+    control_queue(ctx, TrackQueue::resume).await?;
+    Ok(())
+}
+
+/// Skips the message currently being read out!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS"
+)]
+pub async fn skip(ctx: Context<'_>) -> CommandResult {
+    if control_queue(ctx, TrackQueue::skip).await? {
+        ctx.speak_response(ctx.gettext("Skipped the current message!"))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Changes how loud TTS is, as a percentage from 0 to 200!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS"
+)]
+pub async fn volume(
+    ctx: Context<'_>,
+    #[description = "The volume as a percentage, 100 is normal"]
+    #[min = 0]
+    #[max = 200]
+    volume: u16,
+) -> CommandResult {
+    if !channel_check(&ctx, ctx.author_vc()).await? {
+        return Ok(());
+    }
+
+    // Prefix commands skip the slash command range check.
+    if volume > 200 {
+        let msg = ctx.gettext("**Error**: Cannot set the volume above 200%");
+        ctx.send_error(msg.to_owned()).await?;
+        return Ok(());
+    }
+
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    data.guilds_db
+        .set_one(guild_id.into(), "volume", &(volume as i16))
+        .await?;
+
+    // New messages read the volume from the guild row, but queued ones were already created.
+    if let Some(call_lock) = data.songbird.get(guild_id) {
+        let call = call_lock.lock().await;
+        for track in call.queue().current_queue() {
+            drop(track.set_volume(f32::from(volume) / 100.0));
+        }
+    }
+
+    match ctx {
+        poise::Context::Prefix(_) => confirm(ctx).await?,
+        poise::Context::Application(_) => {
+            let msg = ctx
+                .gettext("The volume is now {volume}%")
+                .replace("{volume}", &volume.to_string());
+
+            ctx.say(msg).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads out the last message again!
+#[poise::command(
+    category = "Main Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES | ADD_REACTIONS",
+    aliases("repeat", "again")
+)]
+pub async fn replay(ctx: Context<'_>) -> CommandResult {
+    if !channel_check(&ctx, ctx.author_vc()).await? {
+        return Ok(());
+    }
+
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(call_lock) = data.songbird.get(guild_id) else {
+        ctx.send_not_connected().await?;
+        return Ok(());
+    };
+
+    // The URL already contains the "<user> said" prefix if it was said the first time,
+    // so the replay is exactly what was heard.
+    let Some(url) = data.last_spoken.get(&guild_id).map(|url| url.clone()) else {
+        ctx.say(ctx.gettext("**Error**: Nothing has been read out yet!"))
+            .await?;
+        return Ok(());
+    };
+
+    let clear_generation = data.clear_generation(guild_id);
+    if funcs::enqueue_audio(
+        data,
+        &call_lock,
+        guild_id,
+        ctx.author().id,
+        url,
+        clear_generation,
+    )
+    .await?
+    .is_none()
+    {
+        ctx.say(ctx.gettext("**Error**: I couldn't read out the last message again!"))
+            .await?;
+        return Ok(());
+    }
+
+    match ctx {
+        poise::Context::Prefix(_) => confirm(ctx).await?,
+        poise::Context::Application(_) => {
+            ctx.say(ctx.gettext("Reading out the last message again!"))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `action` on the queue, if there is a message currently being read out.
+///
+/// Returns if the action was run, so the caller can follow up on success.
+async fn control_queue(
+    ctx: Context<'_>,
+    action: fn(&TrackQueue) -> TrackResult<()>,
+) -> Result<bool> {
+    if !channel_check(&ctx, ctx.author_vc()).await? {
+        return Ok(false);
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(call_lock) = ctx.data().songbird.get(guild_id) else {
+        ctx.send_not_connected().await?;
+        return Ok(false);
+    };
+
+    let result = {
+        let call = call_lock.lock().await;
+        let queue = call.queue();
+        (!queue.is_empty()).then(|| action(queue))
+    };
+
+    if let Some(result) = result {
+        result?;
+        confirm(ctx).await?;
+        Ok(true)
+    } else {
+        let msg = ctx.gettext("Nothing is currently being spoken!");
+        ctx.send_error(msg.to_owned()).await?;
+        Ok(false)
+    }
+}
+
+/// Gets the guild's configured confirmation emoji, falling back to 👍 if unset or unusable.
+async fn confirm_emoji(ctx: Context<'_>) -> Result<serenity::ReactionType> {
+    let default = serenity::ReactionType::Unicode(String::from("👍"));
+
+    let guild_id = ctx.guild_id().unwrap();
+    let guild_row = ctx.data().guilds_db.get(guild_id.into()).await?;
+    let Some(emoji) = guild_row.confirm_emoji.as_deref() else {
+        return Ok(default);
+    };
+
+    Ok(match serenity::ReactionType::try_from(emoji) {
+        Ok(emoji) if is_emoji_usable(ctx, &emoji) => emoji,
+        _ => {
+            tracing::debug!("Unusable confirmation emoji {emoji} in {guild_id}, using default");
+            default
+        }
+    })
+}
+
+async fn confirm(ctx: Context<'_>) -> Result<()> {
+    let emoji = confirm_emoji(ctx).await?;
+    match ctx {
+        poise::Context::Prefix(ctx) => {
+            // Prefixed command, just add a reaction. This can be rate limited by rapid commands
+            // such as `-clear`, but the command has already succeeded, so only log the failure.
+            let mut result = ctx.msg.react(ctx.serenity_context(), emoji.clone()).await;
+
+            // The custom emoji may have been deleted since it was checked, so retry with 👍.
+            let default = serenity::ReactionType::Unicode(String::from("👍"));
+            if result.is_err() && emoji != default {
+                result = ctx.msg.react(ctx.serenity_context(), default).await;
+            }
+
+            if let Err(err) = result {
+                tracing::warn!(
+                    "Failed to confirm {} command in {}: {err:?}",
+                    ctx.command.qualified_name,
+                    ctx.msg.channel_id
+                );
+            }
+        }
+        poise::Context::Application(_) => {
+            // Slash command, no message to react to, just say the emoji
+            ctx.say(emoji.to_string()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn commands() -> [Command; 18] {
+    [
+        join(),
+        check_permissions(),
+        summon(),
+        move_(),
+        howto(),
+        leave(),
+        force_leave(),
+        reconnect(),
+        clear(),
+        clear_user(),
+        voicecount(),
+        where_(),
+        queue(),
+        pause(),
+        resume(),
+        skip(),
+        volume(),
+        replay(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::database_models::test_guild_row;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    struct FakeResponder {
+        channel_id: serenity::ChannelId,
+        errors: Mutex<Vec<String>>,
+    }
+
+    impl FakeResponder {
+        fn new(channel_id: u64) -> Self {
+            Self {
+                channel_id: serenity::ChannelId::new(channel_id),
+                errors: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl CheckResponder for FakeResponder {
+        fn channel_id(&self) -> serenity::ChannelId {
+            self.channel_id
+        }
+
+        fn translate<'a>(&'a self, translate: &'a str) -> &'a str {
+            translate
+        }
+
+        async fn respond_error(&self, error_message: String) -> Result<()> {
+            self.errors.lock().unwrap().push(error_message);
+            Ok(())
+        }
+    }
+
+    fn check(
+        responder: &FakeResponder,
+        author_vc: Option<u64>,
+        guild_row: &GuildRow,
+        existing_setup: &[serenity::ChannelId],
+    ) -> bool {
+        let author_vc = author_vc.map(serenity::ChannelId::new);
+        block_on(channel_check_with(
+            responder,
+            author_vc,
+            guild_row,
+            existing_setup,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn channel_check_allows_setup_and_voice_channels() {
+        let mut guild_row = test_guild_row();
+        guild_row.channel = Some(serenity::ChannelId::new(1));
+
+        assert!(check(&FakeResponder::new(1), None, &guild_row, &[]));
+
+        let responder = FakeResponder::new(2);
+        assert!(check(&responder, Some(2), &guild_row, &[]));
+        assert!(responder.errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn channel_check_wrong_channel() {
+        let setup_channel = serenity::ChannelId::new(1);
+        let mut guild_row = test_guild_row();
+        guild_row.channel = Some(setup_channel);
+
+        let responder = FakeResponder::new(2);
+        assert!(!check(&responder, Some(3), &guild_row, &[setup_channel]));
+        assert_eq!(
+            *responder.errors.lock().unwrap(),
+            ["You ran this command in the wrong channel, please move to <#1>."]
+        );
+    }
+
+    #[test]
+    fn channel_check_not_setup() {
+        let responder = FakeResponder::new(2);
+        assert!(!check(&responder, None, &test_guild_row(), &[]));
+        assert_eq!(
+            *responder.errors.lock().unwrap(),
+            ["You haven't setup the bot, please run /setup!"]
+        );
+    }
+
+    #[test]
+    fn channel_check_setup_deleted() {
+        let mut guild_row = test_guild_row();
+        guild_row.channel = Some(serenity::ChannelId::new(1));
+
+        let responder = FakeResponder::new(2);
+        assert!(!check(&responder, None, &guild_row, &[]));
+        assert_eq!(
+            *responder.errors.lock().unwrap(),
+            ["Your setup channel was deleted, please run /setup again!"]
+        );
+    }
+
+    enum FakeFetch {
+        Channels(Vec<u64>),
+        Fails,
+        Hangs,
+    }
+
+    struct FakeChannels {
+        cached: Option<Vec<u64>>,
+        fetch: FakeFetch,
+    }
+
+    impl ChannelSource for FakeChannels {
+        fn cached_channel_ids(&self) -> Option<Vec<serenity::ChannelId>> {
+            let cached = self.cached.as_ref()?;
+            Some(
+                cached
+                    .iter()
+                    .copied()
+                    .map(serenity::ChannelId::new)
+                    .collect(),
+            )
+        }
+
+        async fn fetch_channel_ids(&self) -> Result<Vec<serenity::ChannelId>> {
+            match &self.fetch {
+                FakeFetch::Channels(ids) => {
+                    Ok(ids.iter().copied().map(serenity::ChannelId::new).collect())
+                }
+                FakeFetch::Fails => Err(anyhow::anyhow!("Discord is down")),
+                FakeFetch::Hangs => std::future::pending().await,
+            }
+        }
+    }
+
+    fn existing(source: &FakeChannels, channel_ids: &[u64]) -> Result<Option<Vec<u64>>> {
+        let channel_ids = channel_ids.iter().copied().map(serenity::ChannelId::new);
+        let existing = block_on(existing_channels_with(
+            source,
+            channel_ids.collect(),
+            Duration::from_millis(10),
+        ))?;
+
+        Ok(existing.map(|ids| ids.into_iter().map(serenity::ChannelId::get).collect()))
+    }
+
+    #[test]
+    fn existing_channels_from_cache() {
+        let source = FakeChannels {
+            cached: Some(vec![1, 2]),
+            fetch: FakeFetch::Fails,
+        };
+
+        assert_eq!(existing(&source, &[1, 3]).unwrap(), Some(vec![1]));
+    }
+
+    #[test]
+    fn existing_channels_falls_back_to_fetch() {
+        let source = FakeChannels {
+            cached: None,
+            fetch: FakeFetch::Channels(vec![2, 3]),
+        };
+
+        assert_eq!(existing(&source, &[1, 3]).unwrap(), Some(vec![3]));
+    }
+
+    #[test]
+    fn existing_channels_fetch_fails() {
+        let source = FakeChannels {
+            cached: None,
+            fetch: FakeFetch::Fails,
+        };
+
+        assert!(existing(&source, &[1]).is_err());
+    }
+
+    #[test]
+    fn existing_channels_fetch_times_out() {
+        let source = FakeChannels {
+            cached: None,
+            fetch: FakeFetch::Hangs,
+        };
+
+        assert_eq!(existing(&source, &[1]).unwrap(), None);
+    }
+
+    fn join_failure(err: JoinVCError) -> (&'static str, Option<String>) {
+        let responder = FakeResponder::new(3);
+        (
+            join_error_outcome(&err),
+            join_error_message(&responder, &err),
+        )
+    }
+
+    #[test]
+    fn join_timeout() {
+        let (outcome, msg) = join_failure(songbird::error::JoinError::TimedOut.into());
+        assert_eq!(outcome, "timeout");
+        assert_eq!(
+            msg.as_deref(),
+            Some("I timed out joining your voice channel, please try again!")
+        );
+    }
+
+    #[test]
+    fn join_dropped() {
+        let (outcome, msg) = join_failure(songbird::error::JoinError::Dropped.into());
+        assert_eq!(outcome, "dropped");
+        assert_eq!(
+            msg.as_deref(),
+            Some("I lost connection while joining your voice channel, please try again!")
+        );
+    }
+
+    #[test]
+    fn join_driver_error_is_bubbled_up() {
+        let (outcome, msg) = join_failure(songbird::error::JoinError::NoCall.into());
+        assert_eq!(outcome, "error");
+        assert_eq!(msg, None);
+    }
+
+    #[test]
+    fn join_missing_permissions() {
+        let (outcome, msg) = join_failure(JoinVCError::MissingPermissions {
+            missing: serenity::Permissions::CONNECT,
+            denied_by_overwrites: false,
+        });
+
+        assert_eq!(outcome, "missing_permissions");
+        assert!(msg.unwrap().ends_with("give my role: Connect"));
+
+        let (_, msg) = join_failure(JoinVCError::MissingPermissions {
+            missing: serenity::Permissions::CONNECT,
+            denied_by_overwrites: true,
+        });
+
+        assert!(msg
+            .unwrap()
+            .contains("permission overrides deny me: Connect."));
+    }
+
+    #[test]
+    fn join_channel_full() {
+        let (outcome, msg) = join_failure(JoinVCError::ChannelFull);
+        assert_eq!(outcome, "channel_full");
+        assert!(msg
+            .unwrap()
+            .starts_with("I cannot join your voice channel as it is full"));
+    }
+
+    #[test]
+    fn join_channel_deleted() {
+        let (outcome, msg) = join_failure(JoinVCError::ChannelDeleted);
+        assert_eq!(outcome, "channel_deleted");
+        assert_eq!(
+            msg.as_deref(),
+            Some("Your voice channel no longer exists, please join another and try again!")
+        );
+    }
 }
@@ -14,9 +14,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{borrow::Cow, cmp::Ordering};
+use std::{borrow::Cow, cmp::Ordering, fmt::Write as _};
 
 use anyhow::Error;
+use itertools::Itertools as _;
 use num_format::{Locale, ToFormattedString};
 
 use poise::{
@@ -26,10 +27,11 @@ use poise::{
 
 use crate::{
     constants::OPTION_SEPERATORS,
-    funcs::{fetch_audio, prepare_url},
+    database::GuildRow,
+    funcs::{enqueue_audio, fetch_audio, prepare_url},
     opt_ext::OptionTryUnwrap,
     require_guild,
-    structs::{ApplicationContext, Command, CommandResult, Context, TTSMode},
+    structs::{ApplicationContext, Command, CommandResult, Context, Result, TTSMode},
     traits::PoiseContextExt as _,
 };
 
@@ -103,8 +105,8 @@ pub async fn tts(
         };
 
         if author_voice_cid.is_some() && author_voice_cid == bot_voice_cid {
-            let setup_channel = ctx.data().guilds_db.get(guild_id.into()).await?.channel;
-            if setup_channel == Some(ctx.channel_id()) {
+            let guild_row = ctx.data().guilds_db.get(guild_id.into()).await?;
+            if guild_row.is_setup_channel(ctx.channel_id()) {
                 return Ok(true);
             }
         }
@@ -174,6 +176,120 @@ async fn _tts(ctx: Context<'_>, author: &serenity::User, message: &str) -> Comma
     Ok(())
 }
 
+/// If a test message would be read out as a normal message, following the same rules.
+///
+/// It must be sent in a setup channel or the author's voice channel, by a member with the
+/// required role, who is in the same voice channel as the bot.
+async fn can_play_test(ctx: Context<'_>, guild_row: &GuildRow) -> Result<bool> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(false);
+    };
+
+    let author_vc = ctx.author_vc();
+    let channel_id = ctx.channel_id();
+    if !guild_row.is_setup_channel(channel_id) && author_vc != Some(channel_id) {
+        return Ok(false);
+    }
+
+    if author_vc.is_none() || author_vc != ctx.data().connected_channel(guild_id) {
+        return Ok(false);
+    }
+
+    if let Some(required_role) = guild_row.required_role {
+        let has_role = ctx
+            .author_member()
+            .await
+            .is_some_and(|member| member.roles.contains(&required_role));
+
+        if !has_role && !ctx.author_permissions().await?.administrator() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Tests TTS with your voice, playing it if you could be heard normally!
+#[poise::command(
+    category = "Extra Commands",
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES"
+)]
+pub async fn tts_test(
+    ctx: Context<'_>,
+    #[description = "The text to test TTS with"]
+    #[rest]
+    message: String,
+) -> CommandResult {
+    let data = ctx.data();
+    let author_id = ctx.author().id;
+    let (voice, mode) = data.parse_user_or_guild(author_id, ctx.guild_id()).await?;
+    let speaking_rate = data.speaking_rate(author_id, mode).await?;
+
+    // Outside of a guild, this falls back to the default row.
+    let guild_key = ctx.guild_id().map_or(0, |id| id.get() as i64);
+    let guild_row = data.guilds_db.get(guild_key).await?;
+
+    let url = prepare_url(
+        data.config.tts_service.clone(),
+        &message,
+        &voice,
+        mode,
+        &speaking_rate,
+        &guild_row.msg_length.to_string(),
+    );
+
+    // Playing the audio tests everything, otherwise just test the TTS service.
+    let call = if can_play_test(ctx, &guild_row).await? {
+        ctx.guild_id().and_then(|guild_id| {
+            let call_lock = data.songbird.get(guild_id)?;
+            Some((guild_id, call_lock))
+        })
+    } else {
+        None
+    };
+
+    let (result, played) = if let Some((guild_id, call_lock)) = call {
+        let clear_generation = data.clear_generation(guild_id);
+        let author_id = ctx.author().id;
+        let result =
+            enqueue_audio(data, &call_lock, guild_id, author_id, url, clear_generation).await;
+        (result.map(|track| track.is_some()), true)
+    } else {
+        let auth_key = data.config.tts_service_auth_key.as_deref();
+        let result = fetch_audio(&data.reqwest, url, auth_key).await;
+        (result.map(|audio| audio.is_some()), false)
+    };
+
+    let msg = match result {
+        Ok(true) if played => {
+            ctx.gettext("TTS is working, playing your message in `{voice}` ({mode})!")
+        }
+        Ok(true) => {
+            ctx.gettext("TTS is working, your message was generated in `{voice}` ({mode})!")
+        }
+        Ok(false) => {
+            ctx.gettext("TTS is working, but the TTS service chose not to read your message.")
+        }
+        Err(err) => {
+            tracing::warn!("TTS test failed for {author_id}: {err:?}");
+            let msg = ctx
+                .gettext("TTS failed to generate your message: {error}")
+                .replace("{error}", &err.to_string());
+
+            ctx.send_error(msg).await?;
+            return Ok(());
+        }
+    };
+
+    let mode: &str = mode.into();
+    ctx.say(msg.replace("{voice}", &voice).replace("{mode}", mode))
+        .await?;
+
+    Ok(())
+}
+
 #[poise::command(
     category = "Extra Commands",
     hide_in_help,
@@ -334,14 +450,21 @@ pub async fn channel(ctx: Context<'_>) -> CommandResult {
     let guild_id = ctx.guild_id().unwrap();
     let guild_row = ctx.data().guilds_db.get(guild_id.into()).await?;
 
-    let msg = if let Some(channel) = guild_row.channel
-        && require_guild!(ctx).channels.contains_key(&channel)
-    {
-        if channel == ctx.channel_id() {
+    let setup_channels: Vec<_> = {
+        let guild = require_guild!(ctx);
+        guild_row
+            .setup_channels()
+            .filter(|id| guild.channels.contains_key(id))
+            .collect()
+    };
+
+    let msg = if !setup_channels.is_empty() {
+        if setup_channels.contains(&ctx.channel_id()) {
             String::from(ctx.gettext("You are in the setup channel already!"))
         } else {
-            ctx.gettext("The current setup channel is: <#{channel}>")
-                .replace("{channel}", &channel.to_string())
+            let channels = setup_channels.iter().map(|id| id.mention()).join(", ");
+            ctx.gettext("The current setup channels are: {channels}")
+                .replace("{channels}", &channels)
         }
     } else {
         String::from(ctx.gettext("The channel hasn't been setup, do `/setup #textchannel`"))
@@ -425,15 +548,67 @@ pub async fn invite(ctx: Context<'_>) -> CommandResult {
     Ok(())
 }
 
-pub fn commands() -> [Command; 9] {
+/// Sends a transcript of the last messages read out in this server
+#[poise::command(
+    category = "Extra Commands",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES | ATTACH_FILES"
+)]
+pub async fn transcript(ctx: Context<'_>) -> CommandResult {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+
+    if !data
+        .guilds_db
+        .get(guild_id.into())
+        .await?
+        .flags
+        .transcript()
+    {
+        data.transcripts.remove(&guild_id);
+
+        let msg = ctx.gettext("Transcripts are disabled, enable them with `/set transcript on`");
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+
+    let mut buf = String::new();
+    if let Some(transcript) = data.transcripts.get(&guild_id) {
+        for entry in transcript.iter() {
+            let unix_secs = entry.time.duration_since(std::time::UNIX_EPOCH)?.as_secs();
+            let timestamp = serenity::Timestamp::from_unix_timestamp(unix_secs as i64)?;
+
+            writeln!(buf, "[{timestamp}] {}: {}", entry.speaker, entry.content)?;
+        }
+    }
+
+    if buf.is_empty() {
+        ctx.say(ctx.gettext("No messages have been read out since transcripts were enabled"))
+            .await?;
+        return Ok(());
+    }
+
+    let attachment = serenity::CreateAttachment::bytes(buf.into_bytes(), "transcript.txt");
+    ctx.send(CreateReply::default().attachment(attachment))
+        .await?;
+
+    Ok(())
+}
+
+pub fn commands() -> [Command; 11] {
     [
         tts(),
+        tts_test(),
         uptime(),
         botstats(),
         channel(),
         premium(),
         ping(),
         invite(),
+        transcript(),
         tts_speak(),
         tts_speak_as(),
     ]
@@ -14,18 +14,30 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{borrow::Cow, collections::HashSet, hash::Hash, sync::atomic::Ordering::SeqCst};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt::Write as _,
+    hash::Hash,
+    sync::atomic::Ordering::SeqCst,
+    time::{Duration, Instant},
+};
 
 use self::serenity::builder::*;
 use num_format::{Locale, ToFormattedString};
-use poise::{futures_util::TryStreamExt, serenity_prelude as serenity, CreateReply};
+use poise::{
+    futures_util::{stream, StreamExt as _, TryStreamExt},
+    serenity_prelude as serenity, CreateReply,
+};
 use typesize::TypeSize;
 
 use crate::{
+    constants::{DEFAULT_MSG_LENGTH, TTS_SERVICE_CONCURRENCY},
     database,
     database_models::Compact,
-    funcs::dm_generic,
-    structs::{Command, CommandResult, Context, PrefixContext, TTSModeChoice},
+    funcs::{dm_generic, fetch_audio, prepare_url},
+    opt_ext::OptionTryUnwrap,
+    structs::{Command, CommandResult, Context, Error, PrefixContext, TTSModeChoice},
 };
 
 #[poise::command(prefix_command, owners_only, hide_in_help)]
@@ -305,6 +317,123 @@ pub async fn leave(ctx: Context<'_>) -> CommandResult {
         .map_err(Into::into)
 }
 
+/// Shows how many times join, leave and clear have ended with each outcome since startup
+#[poise::command(prefix_command, owners_only, hide_in_help)]
+pub async fn outcomes(ctx: Context<'_>) -> CommandResult {
+    let counts = ctx.data().outcome_counts();
+    if counts.is_empty() {
+        ctx.say("No outcomes have been recorded yet!").await?;
+        return Ok(());
+    }
+
+    let mut content = String::from("```\n");
+    for (command, outcome, count) in counts {
+        writeln!(content, "{command} {outcome}: {count}")?;
+    }
+    content.push_str("```");
+
+    ctx.say(content).await?;
+    Ok(())
+}
+
+/// Sends the current stats to the bot lists now, instead of waiting for the next update
+#[poise::command(prefix_command, owners_only, hide_in_help)]
+pub async fn update_bot_lists(ctx: Context<'_>) -> CommandResult {
+    let Some(updater) = ctx.data().bot_list_updater.get() else {
+        ctx.say("The bot list updater has not started yet!").await?;
+        return Ok(());
+    };
+
+    let msg = if updater.update_now().await? {
+        "Updated the bot lists!"
+    } else {
+        "Skipped updating the bot lists, the bot is still starting or was just updated."
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+fn percentile(sorted: &[Duration], percent: usize) -> Cow<'static, str> {
+    if sorted.is_empty() {
+        return Cow::Borrowed("N/A");
+    }
+
+    let latency = sorted[((sorted.len() - 1) * percent) / 100];
+    Cow::Owned(format!("{}ms", latency.as_millis()))
+}
+
+/// Benchmarks the latency of the TTS service with `count` requests
+#[poise::command(prefix_command, owners_only, hide_in_help)]
+pub async fn bench(ctx: Context<'_>, count: u16) -> CommandResult {
+    const MAX_COUNT: u16 = 500;
+    const SAMPLE_TEXT: &str = "This is a benchmark of the text to speech service.";
+
+    let data = ctx.data();
+    let author_id = ctx.author().id;
+    let count = count.min(MAX_COUNT);
+
+    let (voice, mode) = data.parse_user_or_guild(author_id, ctx.guild_id()).await?;
+    let speaking_rate = data.speaking_rate(author_id, mode).await?;
+    let msg_length = match ctx.guild_id() {
+        Some(guild_id) => data.guilds_db.get(guild_id.into()).await?.msg_length,
+        None => DEFAULT_MSG_LENGTH,
+    };
+
+    let url = prepare_url(
+        data.config.tts_service.clone(),
+        SAMPLE_TEXT,
+        &voice,
+        mode,
+        &speaking_rate,
+        &msg_length.to_string(),
+    );
+
+    let msg = ctx
+        .say(format!("Sending {count} requests to the TTS service!"))
+        .await?;
+
+    let auth_key = data.config.tts_service_auth_key.as_deref();
+    let mut results = stream::iter(0..count)
+        .map(|_| {
+            let url = url.clone();
+            async move {
+                let start = Instant::now();
+                let audio = fetch_audio(&data.reqwest, url, auth_key).await?;
+
+                // The audio is discarded, but must be fully downloaded to time it.
+                audio.try_unwrap()?.bytes().await?;
+                Ok::<_, Error>(start.elapsed())
+            }
+        })
+        .buffer_unordered(TTS_SERVICE_CONCURRENCY);
+
+    let mut errors: u16 = 0;
+    let mut latencies = Vec::with_capacity(count as usize);
+    while let Some(result) = results.next().await {
+        match result {
+            Ok(latency) => latencies.push(latency),
+            Err(_) => errors += 1,
+        }
+    }
+
+    latencies.sort_unstable();
+
+    let error_rate = (f64::from(errors) / f64::from(count.max(1))) * 100.0;
+    let content = format!(
+        "Done! Sent {count} requests in `{mode}` mode.
+p50: `{}`, p90: `{}`, p99: `{}`
+Error rate: `{error_rate:.1}%`",
+        percentile(&latencies, 50),
+        percentile(&latencies, 90),
+        percentile(&latencies, 99),
+    );
+
+    msg.edit(ctx, CreateReply::default().content(content))
+        .await?;
+    Ok(())
+}
+
 fn get_db_info<CacheKey, RowT>(
     name: &'static str,
     handler: &database::Handler<CacheKey, RowT>,
@@ -445,9 +574,10 @@ pub async fn cache_info(ctx: Context<'_>, kind: Option<String>) -> CommandResult
     Ok(())
 }
 
-pub fn commands() -> [Command; 8] {
+pub fn commands() -> [Command; 11] {
     [
         dm(),
+        bench(),
         close(),
         debug(),
         register(),
@@ -455,5 +585,7 @@ pub fn commands() -> [Command; 8] {
         refresh_ofs(),
         purge_guilds(),
         cache_info(),
+        outcomes(),
+        update_bot_lists(),
     ]
 }
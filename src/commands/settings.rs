@@ -18,13 +18,15 @@ use std::{borrow::Cow, collections::HashMap, fmt::Write};
 
 use self::serenity::{builder::*, ChannelId, ComponentInteractionDataKind, Mentionable};
 use anyhow::bail;
+use arrayvec::ArrayString;
 use database::Compact;
+use itertools::Itertools as _;
 use poise::serenity_prelude as serenity;
 
 use crate::{
     constants::{OPTION_SEPERATORS, PREMIUM_NEUTRAL_COLOUR},
     database,
-    funcs::{confirm_dialog, random_footer},
+    funcs::{confirm_dialog, is_emoji_usable, random_footer},
     opt_ext::OptionGettext,
     require, require_guild,
     structs::{
@@ -81,11 +83,60 @@ pub async fn settings(ctx: Context<'_>) -> CommandResult {
         Cow::Borrowed(none_str)
     };
 
+    let log_channel = match guild_row.log_channel {
+        Some(channel) => Cow::Owned(channel.mention().to_string()),
+        None => Cow::Borrowed(none_str),
+    };
+
+    let control_roles = {
+        let guild = require_guild!(ctx);
+        let mentions = guild_row
+            .control_roles
+            .iter()
+            .filter(|id| guild.roles.contains_key(id))
+            .map(|id| id.mention())
+            .join(", ");
+
+        if mentions.is_empty() {
+            Cow::Borrowed(none_str)
+        } else {
+            Cow::Owned(mentions)
+        }
+    };
+
+    let extra_channels = {
+        let guild = require_guild!(ctx);
+        let mentions = guild_row
+            .extra_channels
+            .iter()
+            .filter(|id| guild.channels.contains_key(id))
+            .map(|id| id.mention())
+            .join(", ");
+
+        if mentions.is_empty() {
+            Cow::Borrowed(none_str)
+        } else {
+            Cow::Owned(mentions)
+        }
+    };
+
     let prefix = &guild_row.prefix;
     let guild_mode = guild_row.voice_mode;
     let nickname = nickname_row.name.as_deref().unwrap_or(none_str);
     let target_lang = guild_row.target_lang.as_deref().unwrap_or(none_str);
+    let default_lang = guild_row.default_lang.as_deref().unwrap_or(none_str);
     let required_role = guild_row.required_role.map(|r| r.mention().to_string());
+    let allowed_voices = if guild_row.allowed_voices.is_empty() {
+        Cow::Borrowed(none_str)
+    } else {
+        Cow::Owned(
+            guild_row
+                .allowed_voices
+                .iter()
+                .map(ArrayString::as_str)
+                .join(", "),
+        )
+    };
 
     let user_mode = if data.premium_check(Some(guild_id)).await?.is_none() {
         userinfo_row.premium_voice_mode
@@ -97,11 +148,17 @@ pub async fn settings(ctx: Context<'_>) -> CommandResult {
         .guild_voice_db
         .get((guild_id.into(), guild_mode))
         .await?;
+    let lang_voice;
     let default_voice = {
-        if guild_voice_row.guild_id.is_none() {
-            Cow::Borrowed(guild_mode.default_voice())
-        } else {
+        if guild_voice_row.guild_id.is_some() {
             format_voice(data, &guild_voice_row.voice, guild_mode)
+        } else if let Some(lang) = &guild_row.default_lang
+            && let Some(voice) = data.voice_for_language(guild_mode, lang)
+        {
+            lang_voice = voice;
+            format_voice(data, &lang_voice, guild_mode)
+        } else {
+            Cow::Borrowed(guild_mode.default_voice())
         }
     };
 
@@ -148,13 +205,19 @@ pub async fn settings(ctx: Context<'_>) -> CommandResult {
 
         .field(ctx.gettext("**General Server Settings**"), &ctx.gettext("
 {sep1} Setup Channel: {channel_mention}
+{sep1} Extra Channels: {extra_channels}
 {sep1} Required Role: {role_mention}
+{sep1} Control Roles: {control_roles}
+{sep1} Log Channel: {log_channel}
 {sep1} Command Prefix: `{prefix}`
 {sep1} Auto Join: `{autojoin}`
         ")
             .replace("{sep1}", sep1)
             .replace("{prefix}", prefix)
             .replace("{channel_mention}", &channel_mention)
+            .replace("{extra_channels}", &extra_channels)
+            .replace("{control_roles}", &control_roles)
+            .replace("{log_channel}", &log_channel)
             .replace("{autojoin}", &guild_row.flags.auto_join().to_string())
             .replace("{role_mention}", required_role.as_deref().unwrap_or(none_str)),
         false)
@@ -162,25 +225,55 @@ pub async fn settings(ctx: Context<'_>) -> CommandResult {
 {sep2} <User> said: message: `{xsaid}`
 {sep2} Ignore bot's messages: `{bot_ignore}`
 {sep2} Ignore audience messages: `{audience_ignore}`
+{sep2} Ignore mention only messages: `{mention_only_ignore}`
 {sep2} Require users in voice channel: `{require_voice}`
 {sep2} Required prefix for TTS: `{required_prefix}`
+{sep2} Ignored prefix for TTS: `{ignore_prefix}`
+{sep2} Allowed voices: `{allowed_voices}`
+{sep2} Speak command responses: `{speak_responses}`
+{sep2} Announce voice channel joins/leaves: `{announce_voice}`
+{sep2} React to messages once read: `{read_receipts}`
+{sep2} Keep a transcript: `{transcript}`
+{sep2} Confirmation emoji: {confirm_emoji}
 
 **{sep2} Default Server Voice Mode: `{guild_mode}`**
 **{sep2} Default Server Voice: `{default_voice}`**
+{sep2} Default Server Language: `{default_lang}`
+{sep2} Response Language: `{language}`
 
 {sep2} Max Time to Read: `{msg_length} seconds`
 {sep2} Max Repeated Characters: `{repeated_chars}`
+{sep2} Max Characters: `{max_chars}`
+{sep2} Say when truncated: `{truncation_notice}`
+{sep2} Max Queue Length: `{max_queue_length}`
+{sep2} Volume: `{volume}%`
+{sep2} Drop oldest message when queue is full: `{queue_drop_oldest}`
         ")
             .replace("{sep2}", sep2)
             .replace("{xsaid}", &guild_row.flags.xsaid().to_string())
             .replace("{bot_ignore}", &guild_row.flags.bot_ignore().to_string())
             .replace("{audience_ignore}", &guild_row.flags.audience_ignore().to_string())
+            .replace("{mention_only_ignore}", &guild_row.flags.mention_only_ignore().to_string())
             .replace("{require_voice}", &guild_row.flags.require_voice().to_string())
             .replace("{required_prefix}", guild_row.required_prefix.as_deref().unwrap_or(none_str))
+            .replace("{ignore_prefix}", guild_row.ignore_prefix.as_deref().unwrap_or(none_str))
+            .replace("{allowed_voices}", &allowed_voices)
+            .replace("{speak_responses}", &guild_row.flags.speak_responses().to_string())
+            .replace("{announce_voice}", &guild_row.flags.announce_voice().to_string())
+            .replace("{read_receipts}", &guild_row.flags.read_receipts().to_string())
+            .replace("{transcript}", &guild_row.flags.transcript().to_string())
+            .replace("{confirm_emoji}", guild_row.confirm_emoji.as_deref().unwrap_or("👍"))
             .replace("{guild_mode}", guild_mode.into())
             .replace("{default_voice}", &default_voice)
+            .replace("{default_lang}", default_lang)
+            .replace("{language}", guild_row.language.as_deref().unwrap_or(none_str))
             .replace("{msg_length}", &guild_row.msg_length.to_string())
-            .replace("{repeated_chars}", &guild_row.repeated_chars.to_string()),
+            .replace("{repeated_chars}", &guild_row.repeated_chars.to_string())
+            .replace("{max_chars}", &guild_row.max_chars.to_string())
+            .replace("{truncation_notice}", &guild_row.flags.truncation_notice().to_string())
+            .replace("{max_queue_length}", &guild_row.max_queue_length.to_string())
+            .replace("{volume}", &guild_row.volume.to_string())
+            .replace("{queue_drop_oldest}", &guild_row.flags.queue_drop_oldest().to_string()),
         false)
         .field(ctx.gettext("**Translation Settings (Premium Only)**"), &ctx.gettext("
 {sep4} Translation: `{to_translate}`
@@ -524,7 +617,17 @@ where
     let data = ctx.data();
     let (_, mode) = data.parse_user_or_guild(author_id, Some(guild_id)).await?;
     Ok(if let Some(voice) = voice {
-        if check_valid_voice(data, &voice, mode) {
+        if matches!(target, Target::User)
+            && !data
+                .guilds_db
+                .get(guild_id.into())
+                .await?
+                .is_voice_allowed(&voice)
+        {
+            Cow::Borrowed(ctx.gettext(
+                "This server does not allow that voice, do `/voices` to see the allowed voices",
+            ))
+        } else if check_valid_voice(data, &voice, mode) {
             general_db.create_row(key).await?;
             voice_db.set_one((key, mode), "voice", &voice).await?;
 
@@ -583,6 +686,19 @@ fn check_valid_voice(data: &Data, code: &String, mode: TTSMode) -> bool {
     }
 }
 
+const ALL_MODES: [TTSMode; 4] = [
+    TTSMode::gTTS,
+    TTSMode::eSpeak,
+    TTSMode::Polly,
+    TTSMode::gCloud,
+];
+
+fn check_valid_voice_any_mode(data: &Data, code: &String) -> bool {
+    ALL_MODES
+        .into_iter()
+        .any(|mode| check_valid_voice(data, code, mode))
+}
+
 fn to_enabled(catalog: Option<&gettext::Catalog>, value: bool) -> &str {
     if value {
         catalog.gettext("Enabled")
@@ -644,7 +760,8 @@ async fn generic_bool_command(
         .guilds_db
         .set_one(ctx.guild_id().unwrap().into(), key, &value)
         .await?;
-    ctx.say(resp).await?;
+    ctx.say(resp.clone()).await?;
+    ctx.speak_response(&resp).await?;
 
     Ok(())
 }
@@ -717,6 +834,22 @@ create_bool_command!(
     gettext("Requiring users to be in voice channel for TTS is now: {}"),
     aliases("voice_require", "require_in_vc"),
 );
+create_bool_command!(
+    "Makes the bot say when a message was cut short by the max characters setting",
+    "Whether to say when a message is truncated",
+    truncation_notice,
+    "truncation_notice",
+    gettext("Saying when a message is truncated is now: {}"),
+    aliases("truncate_notice"),
+);
+create_bool_command!(
+    "Makes the bot drop the oldest queued message when the queue is full, instead of the newest",
+    "Whether to drop the oldest queued message when the queue is full",
+    queue_drop_oldest,
+    "queue_drop_oldest",
+    gettext("Dropping the oldest message from a full queue is now: {}"),
+    aliases("drop_oldest"),
+);
 create_bool_command!(
     "Makes the bot ignore messages sent by members of the audience in stage channels",
     "Whether to ignore messages sent by the audience",
@@ -725,6 +858,46 @@ create_bool_command!(
     gettext("Ignoring audience is now: {}"),
     aliases("audienceignore", "ignore_audience", "ignoreaudience"),
 );
+create_bool_command!(
+    "Makes the bot say its command responses, such as the join message, in the voice channel",
+    "Whether to say command responses in the voice channel",
+    speak_responses,
+    "speak_responses",
+    gettext("Speaking command responses is now: {}"),
+    aliases("speakresponses", "say_responses"),
+);
+create_bool_command!(
+    "Makes the bot say when users join or leave the voice channel it is in",
+    "Whether to announce users joining and leaving the voice channel",
+    announce_voice,
+    "announce_voice",
+    gettext("Announcing voice channel joins and leaves is now: {}"),
+    aliases("announcevoice", "announce_joins", "voice_announce"),
+);
+create_bool_command!(
+    "Makes the bot react to messages with 🔊 once they have been read out",
+    "Whether to react to messages once they have been read out",
+    read_receipts,
+    "read_receipts",
+    gettext("Read receipts are now: {}"),
+    aliases("readreceipts", "read_receipt", "receipts"),
+);
+create_bool_command!(
+    "Makes the bot ignore messages that are only mentions, such as a lone ping",
+    "Whether to ignore messages that are only mentions",
+    mention_only_ignore,
+    "mention_only_ignore",
+    gettext("Ignoring mention only messages is now: {}"),
+    aliases("mentiononlyignore", "ignore_mentions", "ignore_pings"),
+);
+create_bool_command!(
+    "Makes the bot keep a transcript of the last messages it has read out, see `/transcript`",
+    "Whether to keep a transcript of read out messages",
+    transcript,
+    "transcript",
+    gettext("Keeping a transcript is now: {}"),
+    aliases("transcripts", "keep_transcript"),
+);
 create_bool_command!(
     "Whether to use DeepL translate to translate all TTS messages to the same language ",
     "Whether to translate all messages to the same language",
@@ -870,6 +1043,135 @@ async fn required_prefix(
     Ok(())
 }
 
+/// Changes the prefix for messages which should not be read out, such as `!` for other bots.
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES"
+)]
+async fn ignore_prefix(
+    ctx: Context<'_>,
+    #[description = "Messages starting with this will not be read out, leave empty to read all"]
+    ignore_prefix: Option<String>,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+    let ignore_prefix = ignore_prefix.filter(|prefix| !prefix.is_empty());
+    if let Some(prefix) = ignore_prefix.as_deref()
+        && let Err(err) = check_prefix(&ctx, prefix)
+    {
+        ctx.say(err).await?;
+    } else {
+        ctx.data()
+            .guilds_db
+            .set_one(guild_id.into(), "ignore_prefix", &ignore_prefix)
+            .await?;
+        let msg = match ignore_prefix.as_deref() {
+            Some(prefix) => ctx
+                .gettext("Messages starting with {} will no longer be read out")
+                .replace("{}", prefix),
+            None => ctx.gettext("All messages will now be read out").to_owned(),
+        };
+
+        ctx.say(msg).await?;
+    }
+
+    Ok(())
+}
+
+/// Changes the emoji used to confirm commands such as `/clear`.
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES"
+)]
+async fn confirm_emoji(
+    ctx: Context<'_>,
+    #[description = "The emoji to confirm commands with"] emoji: Option<String>,
+) -> CommandResult {
+    if let Some(emoji) = emoji.as_deref() {
+        let usable =
+            serenity::ReactionType::try_from(emoji).is_ok_and(|emoji| is_emoji_usable(ctx, &emoji));
+
+        if !usable || emoji.len() > 64 {
+            let msg = ctx.gettext("**Error**: `{emoji}` is not an emoji I can use, please use a normal emoji or one from this server!")
+                .replace("{emoji}", emoji);
+
+            ctx.say(msg).await?;
+            return Ok(());
+        }
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .guilds_db
+        .set_one(guild_id.into(), "confirm_emoji", &emoji)
+        .await?;
+
+    ctx.say(
+        ctx.gettext("Commands will now be confirmed with: {}")
+            .replace("{}", emoji.as_deref().unwrap_or("👍")),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Changes the language TTS Bot responds in, instead of each user's Discord language
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("bot_language", "response_lang")
+)]
+async fn response_language(
+    ctx: Context<'_>,
+    #[description = "The language to respond in, leave blank to use each user's language"]
+    language: Option<String>,
+) -> CommandResult {
+    let data = ctx.data();
+    if let Some(language) = language.as_deref()
+        && !data.translations.contains_key(language)
+    {
+        let languages = data.translations.keys().sorted().join(", ");
+        let msg = ctx
+            .gettext("**Error**: `{language}` is not a supported language, please pick from: {languages}")
+            .replace("{language}", language)
+            .replace("{languages}", &languages);
+
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    data.guilds_db
+        .set_one(guild_id.into(), "language", &language)
+        .await?;
+
+    // Re-cache the row, so this response is already in the new language.
+    data.guilds_db.get(guild_id.into()).await?;
+
+    let msg = match language.as_deref() {
+        Some(language) => ctx
+            .gettext("TTS Bot will now respond in: {}")
+            .replace("{}", language),
+        None => ctx
+            .gettext("TTS Bot will now respond in each user's language")
+            .to_owned(),
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
 /// Changes the default mode for TTS that messages are read in
 #[poise::command(
     guild_only,
@@ -912,13 +1214,7 @@ pub async fn server_mode(
     slash_command,
     required_permissions = "ADMINISTRATOR",
     required_bot_permissions = "SEND_MESSAGES",
-    aliases(
-        "defaultlang",
-        "default_lang",
-        "defaultlang",
-        "slang",
-        "serverlanguage"
-    )
+    aliases("slang", "serverlanguage")
 )]
 pub async fn server_voice(
     ctx: Context<'_>,
@@ -946,6 +1242,290 @@ pub async fn server_voice(
     Ok(())
 }
 
+/// Changes the language the default server voice is picked from
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("defaultlang", "default_language", "server_lang")
+)]
+pub async fn default_lang(
+    ctx: Context<'_>,
+    #[description = "The language to read messages in, such as `fr` or `en-GB`"] language: Option<
+        String,
+    >,
+) -> CommandResult {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+
+    let Some(language) = language else {
+        data.guilds_db
+            .set_one(guild_id.into(), "default_lang", &None::<String>)
+            .await?;
+
+        ctx.say(ctx.gettext("Reset the default server language"))
+            .await?;
+        return Ok(());
+    };
+
+    let is_supported = language.len() <= 5
+        && ALL_MODES
+            .into_iter()
+            .any(|mode| data.voice_for_language(mode, &language).is_some());
+
+    if !is_supported {
+        let msg = ctx
+            .gettext("**Error**: Unsupported language, do `/voices` to see the supported voices");
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+
+    data.guilds_db
+        .set_one(guild_id.into(), "default_lang", &language)
+        .await?;
+
+    let mut to_say = ctx
+        .gettext("The default server language is now: `{language}`")
+        .replace("{language}", &language);
+
+    let guild_mode = data.guilds_db.get(guild_id.into()).await?.voice_mode;
+    let guild_voice_row = data
+        .guild_voice_db
+        .get((guild_id.into(), guild_mode))
+        .await?;
+    if guild_voice_row.guild_id.is_some() {
+        to_say.push_str(
+            ctx.gettext("\nThis server has a default voice set, which will be used instead"),
+        );
+    }
+
+    ctx.say(to_say).await?;
+    Ok(())
+}
+
+/// Sets extra channels TTS Bot will read from, alongside the setup channel
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("extra_channel", "extrachannels")
+)]
+pub async fn extra_channels(
+    ctx: Context<'_>,
+    #[description = "Space separated list of channels to also read from, leave blank to reset"]
+    #[rest]
+    channels: Option<String>,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let mut channel_ids = Vec::new();
+    for channel in channels.as_deref().unwrap_or_default().split_whitespace() {
+        let channel_id = channel
+            .trim_start_matches("<#")
+            .trim_end_matches('>')
+            .parse::<ChannelId>()
+            .ok()
+            .filter(|id| {
+                require_guild!(ctx, false)
+                    .channels
+                    .get(id)
+                    .is_some_and(|c| c.is_text_based())
+            });
+
+        let Some(channel_id) = channel_id else {
+            let msg = ctx
+                .gettext("**Error**: `{channel}` is not a text channel in this server")
+                .replace("{channel}", channel);
+
+            ctx.say(msg).await?;
+            return Ok(());
+        };
+
+        if !channel_ids.contains(&channel_id) {
+            channel_ids.push(channel_id);
+        }
+    }
+
+    let db_channel_ids: Vec<i64> = channel_ids.iter().map(|id| id.get() as i64).collect();
+    ctx.data()
+        .guilds_db
+        .set_one(guild_id.into(), "extra_channels", &db_channel_ids)
+        .await?;
+
+    let msg = if channel_ids.is_empty() {
+        Cow::Borrowed(ctx.gettext("TTS Bot will now only read from the setup channel"))
+    } else {
+        let channels = channel_ids.iter().map(|id| id.mention()).join(", ");
+        Cow::Owned(
+            ctx.gettext("TTS Bot will now also read from: {channels}")
+                .replace("{channels}", &channels),
+        )
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Restricts making TTS Bot join and leave to some roles, leave blank to allow everyone
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("control_role", "controlroles")
+)]
+pub async fn control_roles(
+    ctx: Context<'_>,
+    #[description = "Space separated list of roles allowed to control the bot, leave blank to reset"]
+    #[rest]
+    roles: Option<String>,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let mut role_ids = Vec::new();
+    for role in roles.as_deref().unwrap_or_default().split_whitespace() {
+        let role_id = role
+            .trim_start_matches("<@&")
+            .trim_end_matches('>')
+            .parse::<serenity::RoleId>()
+            .ok()
+            .filter(|id| require_guild!(ctx, false).roles.contains_key(id));
+
+        let Some(role_id) = role_id else {
+            let msg = ctx
+                .gettext("**Error**: `{role}` is not a role in this server")
+                .replace("{role}", role);
+
+            ctx.say(msg).await?;
+            return Ok(());
+        };
+
+        if !role_ids.contains(&role_id) {
+            role_ids.push(role_id);
+        }
+    }
+
+    let db_role_ids: Vec<i64> = role_ids.iter().map(|id| id.get() as i64).collect();
+    ctx.data()
+        .guilds_db
+        .set_one(guild_id.into(), "control_roles", &db_role_ids)
+        .await?;
+
+    let msg = if role_ids.is_empty() {
+        Cow::Borrowed(ctx.gettext("Everyone can now make TTS Bot join and leave"))
+    } else {
+        let roles = role_ids.iter().map(|id| id.mention()).join(", ");
+        Cow::Owned(
+            ctx.gettext("Only administrators and {roles} can now make TTS Bot join and leave")
+                .replace("{roles}", &roles),
+        )
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Sets a channel to log TTS Bot joining and leaving voice channels, leave blank to disable
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("logchannel", "log")
+)]
+pub async fn log_channel(
+    ctx: Context<'_>,
+    #[description = "The channel to log joins and leaves to"]
+    #[channel_types("Text")]
+    channel: Option<serenity::GuildChannel>,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .guilds_db
+        .set_one(
+            guild_id.into(),
+            "log_channel",
+            &channel.as_ref().map(|c| c.id.get() as i64),
+        )
+        .await?;
+
+    let msg = match channel {
+        Some(channel) => ctx
+            .gettext("TTS Bot will now log joins and leaves to {channel}")
+            .replace("{channel}", &channel.mention().to_string()),
+        None => ctx
+            .gettext("TTS Bot will no longer log joins and leaves")
+            .to_owned(),
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Restricts the voices users can pick, leave blank to allow all voices
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("voice_allowlist", "allowed_voice", "allowedvoices")
+)]
+pub async fn allowed_voices(
+    ctx: Context<'_>,
+    #[description = "Comma separated list of voices users can pick from, leave blank to reset"]
+    #[rest]
+    voices: Option<String>,
+) -> CommandResult {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+
+    let voices: Vec<String> = voices
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect();
+
+    if let Some(invalid_voice) = voices.iter().find(|v| !check_valid_voice_any_mode(data, v)) {
+        let msg = ctx
+            .gettext("**Error**: Invalid voice `{voice}`, do `/voices`")
+            .replace("{voice}", invalid_voice);
+
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+
+    data.guilds_db
+        .set_one(guild_id.into(), "allowed_voices", &voices)
+        .await?;
+
+    let msg = if voices.is_empty() {
+        Cow::Borrowed(ctx.gettext("Users can now pick any voice"))
+    } else {
+        Cow::Owned(
+            ctx.gettext("Users can now only pick from: {voices}")
+                .replace("{voices}", &format_languages(voices.iter())),
+        )
+    };
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
 /// Changes the target language for translation
 #[poise::command(
     guild_only,
@@ -1025,6 +1605,116 @@ pub async fn command_prefix(
     Ok(())
 }
 
+/// Changes the max number of characters to read out of a message (0 = off)
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("max_characters", "max_length", "char_limit")
+)]
+pub async fn max_chars(
+    ctx: Context<'_>,
+    #[description = "The max characters to read, leave blank to see the current limit"]
+    chars: Option<u16>,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(chars) = chars else {
+        let current = ctx.data().guilds_db.get(guild_id.into()).await?.max_chars;
+        let msg = if current == 0 {
+            Cow::Borrowed(ctx.gettext("There is no limit on the number of characters read out"))
+        } else {
+            Cow::Owned(
+                ctx.gettext("The max characters read out is currently: {}")
+                    .replace("{}", &current.to_string()),
+            )
+        };
+
+        ctx.say(msg).await?;
+        return Ok(());
+    };
+
+    let to_send = {
+        if chars > 1500 {
+            Cow::Borrowed(ctx.gettext("**Error**: Cannot set the max characters above 1500"))
+        } else if chars < 50 && chars != 0 {
+            Cow::Borrowed(ctx.gettext("**Error**: Cannot set the max characters below 50"))
+        } else {
+            ctx.data()
+                .guilds_db
+                .set_one(guild_id.into(), "max_chars", &(chars as i16))
+                .await?;
+            Cow::Owned(
+                ctx.gettext("Max characters read out is now: {}")
+                    .replace("{}", &chars.to_string()),
+            )
+        }
+    };
+
+    ctx.say(to_send).await?;
+    Ok(())
+}
+
+/// Changes the max number of messages that can be queued (0 = off)
+#[poise::command(
+    guild_only,
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("queue_length", "max_queue", "queue_limit")
+)]
+pub async fn max_queue_length(
+    ctx: Context<'_>,
+    #[description = "The max queued messages, leave blank to see the current limit"] length: Option<
+        u8,
+    >,
+) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(length) = length else {
+        let current = ctx
+            .data()
+            .guilds_db
+            .get(guild_id.into())
+            .await?
+            .max_queue_length;
+        let msg = if current == 0 {
+            Cow::Borrowed(ctx.gettext("There is no limit on the number of queued messages"))
+        } else {
+            Cow::Owned(
+                ctx.gettext("The max queue length is currently: {}")
+                    .replace("{}", &current.to_string()),
+            )
+        };
+
+        ctx.say(msg).await?;
+        return Ok(());
+    };
+
+    let to_send = {
+        if length > 100 {
+            Cow::Borrowed(ctx.gettext("**Error**: Cannot set the max queue length above 100"))
+        } else if length < 5 && length != 0 {
+            Cow::Borrowed(ctx.gettext("**Error**: Cannot set the max queue length below 5"))
+        } else {
+            ctx.data()
+                .guilds_db
+                .set_one(guild_id.into(), "max_queue_length", &(length as i16))
+                .await?;
+            Cow::Owned(
+                ctx.gettext("Max queue length is now: {}")
+                    .replace("{}", &length.to_string()),
+            )
+        }
+    };
+
+    ctx.say(to_send).await?;
+    Ok(())
+}
+
 /// Changes the max repetion of a character (0 = off)
 #[poise::command(
     guild_only,
@@ -1174,6 +1864,64 @@ pub async fn speaking_rate(
     Ok(())
 }
 
+/// Stops your messages from being added to server transcripts
+#[poise::command(
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("transcriptoptout", "no_transcript")
+)]
+pub async fn transcript_opt_out(
+    ctx: Context<'_>,
+    #[description = "Whether to keep your messages out of transcripts"] value: bool,
+) -> CommandResult {
+    let data = ctx.data();
+    let author_id = ctx.author().id;
+
+    data.userinfo_db
+        .set_one(author_id.into(), "transcript_opt_out", &value)
+        .await?;
+
+    if value {
+        for mut transcript in data.transcripts.iter_mut() {
+            transcript.retain(|entry| entry.user_id != author_id);
+        }
+    }
+
+    let msg = ctx
+        .gettext("Opting out of transcripts is now: {}")
+        .replace("{}", to_enabled(ctx.current_catalog(), value));
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Stops your name being said before your messages, even if the server has xsaid on
+#[poise::command(
+    category = "Settings",
+    prefix_command,
+    slash_command,
+    required_bot_permissions = "SEND_MESSAGES",
+    aliases("xsaidoptout", "no_xsaid")
+)]
+pub async fn xsaid_opt_out(
+    ctx: Context<'_>,
+    #[description = "Whether to keep your name from being said before your messages"] value: bool,
+) -> CommandResult {
+    ctx.data()
+        .userinfo_db
+        .set_one(ctx.author().id.into(), "xsaid_opt_out", &value)
+        .await?;
+
+    let msg = ctx
+        .gettext("Opting out of xsaid is now: {}")
+        .replace("{}", to_enabled(ctx.current_catalog(), value));
+
+    ctx.say(msg).await?;
+    Ok(())
+}
+
 /// Replaces your username in "<user> said" with a given name
 #[poise::command(
     guild_only,
@@ -1586,6 +2334,35 @@ pub async fn voices(
         None => data.parse_user_or_guild(author.id, ctx.guild_id()).await?.1,
     };
 
+    // The voice lists are fetched from the TTS service at startup, so may be empty if it was down.
+    let no_voices = match mode {
+        TTSMode::eSpeak => data.espeak_voices.is_empty(),
+        TTSMode::gTTS => data.gtts_voices.is_empty(),
+        TTSMode::Polly => data.polly_voices.is_empty(),
+        TTSMode::gCloud => data.gcloud_voices.is_empty(),
+    };
+
+    if no_voices {
+        let msg = ctx
+            .gettext("The voices for `{mode}` are currently unavailable, please try again later!")
+            .replace("{mode}", mode.into());
+
+        ctx.send_error(msg).await?;
+        return Ok(());
+    }
+
+    let guild_row = if let Some(guild_id) = ctx.guild_id() {
+        Some(data.guilds_db.get(guild_id.into()).await?)
+    } else {
+        None
+    };
+
+    let is_allowed = |voice: &str| {
+        guild_row
+            .as_ref()
+            .map_or(true, |row| row.is_voice_allowed(voice))
+    };
+
     let voices = {
         let random_footer = || {
             random_footer(
@@ -1596,11 +2373,15 @@ pub async fn voices(
         };
 
         match mode {
-            TTSMode::eSpeak => format_languages(data.espeak_voices.iter()),
-            TTSMode::gTTS => format_languages(data.gtts_voices.keys()),
+            TTSMode::eSpeak => {
+                format_languages(data.espeak_voices.iter().filter(|v| is_allowed(v.as_str())))
+            }
+            TTSMode::gTTS => {
+                format_languages(data.gtts_voices.keys().filter(|v| is_allowed(v.as_str())))
+            }
             TTSMode::Polly => {
                 return {
-                    let (current_voice, pages) = list_polly_voices(&ctx).await?;
+                    let (current_voice, pages) = list_polly_voices(&ctx, is_allowed).await?;
                     MenuPaginator::new(ctx, pages, current_voice, mode, random_footer())
                         .start()
                         .await
@@ -1609,7 +2390,7 @@ pub async fn voices(
             }
             TTSMode::gCloud => {
                 return {
-                    let (current_voice, pages) = list_gcloud_voices(&ctx).await?;
+                    let (current_voice, pages) = list_gcloud_voices(&ctx, is_allowed).await?;
                     MenuPaginator::new(ctx, pages, current_voice, mode, random_footer())
                         .start()
                         .await
@@ -1657,7 +2438,10 @@ pub async fn voices(
     Ok(())
 }
 
-pub async fn list_polly_voices(ctx: &Context<'_>) -> Result<(String, Vec<String>)> {
+pub async fn list_polly_voices(
+    ctx: &Context<'_>,
+    is_allowed: impl Fn(&str) -> bool,
+) -> Result<(String, Vec<String>)> {
     let data = ctx.data();
 
     let (voice_id, mode) = data
@@ -1672,14 +2456,14 @@ pub async fn list_polly_voices(ctx: &Context<'_>) -> Result<(String, Vec<String>
     };
 
     let mut lang_to_voices: HashMap<_, Vec<_>> = HashMap::new();
-    for voice in data.polly_voices.values() {
+    for voice in data.polly_voices.values().filter(|v| is_allowed(&v.id)) {
         lang_to_voices
             .entry(&voice.language_name)
             .or_default()
             .push(voice);
     }
 
-    let pages = lang_to_voices
+    let mut pages: Vec<_> = lang_to_voices
         .into_values()
         .map(|voices| {
             let mut buf = String::with_capacity(voices.len() * 12);
@@ -1695,13 +2479,20 @@ pub async fn list_polly_voices(ctx: &Context<'_>) -> Result<(String, Vec<String>
         })
         .collect::<Result<_>>()?;
 
+    if pages.is_empty() {
+        pages.push(String::from(ctx.gettext("None")));
+    }
+
     Ok((
         format!("{} - {} ({})", voice.id, voice.language_name, voice.gender),
         pages,
     ))
 }
 
-pub async fn list_gcloud_voices(ctx: &Context<'_>) -> Result<(String, Vec<String>)> {
+pub async fn list_gcloud_voices(
+    ctx: &Context<'_>,
+    is_allowed: impl Fn(&str) -> bool,
+) -> Result<(String, Vec<String>)> {
     let data = ctx.data();
 
     let (lang_variant, mode) = data
@@ -1714,27 +2505,115 @@ pub async fn list_gcloud_voices(ctx: &Context<'_>) -> Result<(String, Vec<String
     .split_once(' ')
     .unwrap();
 
-    let pages = data
+    let mut pages: Vec<_> = data
         .gcloud_voices
         .iter()
         .map(|(language, variants)| {
             let mut buf = String::with_capacity(variants.len() * 12);
             for (variant, gender) in variants {
-                writeln!(buf, "{language} {variant} ({gender})")?;
+                if is_allowed(&format!("{language} {variant}")) {
+                    writeln!(buf, "{language} {variant} ({gender})")?;
+                }
             }
 
             anyhow::Ok(buf)
         })
+        .filter(|page| page.as_ref().map_or(true, |p| !p.is_empty()))
         .collect::<Result<_>>()?;
 
+    if pages.is_empty() {
+        pages.push(String::from(ctx.gettext("None")));
+    }
+
     let gender = data.gcloud_voices[lang][variant];
     Ok((format!("{lang} {variant} ({gender})"), pages))
 }
 
-pub fn commands() -> [Command; 5] {
+/// Exports this server's settings as a JSON file
+#[poise::command(
+    category = "Settings",
+    guild_only,
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    required_bot_permissions = "SEND_MESSAGES | ATTACH_FILES",
+    aliases("export", "settings_export")
+)]
+pub async fn export_settings(ctx: Context<'_>) -> CommandResult {
+    let guild_id = ctx.guild_id().unwrap();
+
+    // Guilds without a row get the default row, so this is always a complete set of settings.
+    let data = ctx.data();
+    let row = data.guilds_db.get(guild_id.into()).await?;
+    let flags = &row.flags;
+
+    // Only set if the guild has picked a voice for its mode, otherwise the mode's default is used.
+    let voice_row = data
+        .guild_voice_db
+        .get((guild_id.into(), row.voice_mode))
+        .await?;
+
+    let guild_voice = voice_row.guild_id.map(|_| {
+        serenity::json::json!({
+            "mode": voice_row.mode.to_string(),
+            "voice": voice_row.voice.as_str(),
+        })
+    });
+
+    let settings = serenity::json::json!({
+        "guild_id": guild_id,
+        "channel": row.channel,
+        "extra_channels": row.extra_channels,
+        "control_roles": row.control_roles,
+        "log_channel": row.log_channel,
+        "premium_user": row.premium_user,
+        "required_role": row.required_role,
+        "xsaid": flags.xsaid(),
+        "auto_join": flags.auto_join(),
+        "bot_ignore": flags.bot_ignore(),
+        "to_translate": flags.to_translate(),
+        "require_voice": flags.require_voice(),
+        "audience_ignore": flags.audience_ignore(),
+        "speak_responses": flags.speak_responses(),
+        "announce_voice": flags.announce_voice(),
+        "read_receipts": flags.read_receipts(),
+        "mention_only_ignore": flags.mention_only_ignore(),
+        "transcript": flags.transcript(),
+        "queue_drop_oldest": flags.queue_drop_oldest(),
+        "truncation_notice": flags.truncation_notice(),
+        "msg_length": row.msg_length,
+        "repeated_chars": row.repeated_chars,
+        "max_queue_length": row.max_queue_length,
+        "volume": row.volume,
+        "max_chars": row.max_chars,
+        "prefix": row.prefix.as_str(),
+        "target_lang": row.target_lang.as_deref(),
+        "default_lang": row.default_lang.as_deref(),
+        "language": row.language.as_deref(),
+        "required_prefix": row.required_prefix.as_deref(),
+        "ignore_prefix": row.ignore_prefix.as_deref(),
+        "confirm_emoji": row.confirm_emoji.as_deref(),
+        "allowed_voices": row.allowed_voices.iter().map(ArrayString::as_str).collect::<Vec<_>>(),
+        "voice_mode": row.voice_mode.to_string(),
+        "guild_voice": guild_voice,
+    });
+
+    let attachment = serenity::CreateAttachment::bytes(
+        serenity::json::to_vec(&settings)?,
+        format!("settings-{guild_id}.json"),
+    );
+
+    ctx.send(poise::CreateReply::default().attachment(attachment))
+        .await?;
+
+    Ok(())
+}
+
+pub fn commands() -> [Command; 6] {
     [
         settings(),
         setup(),
+        export_settings(),
         voices(),
         translation_languages(),
         poise::Command {
@@ -1743,11 +2622,17 @@ pub fn commands() -> [Command; 5] {
                     name: String::from("channel"),
                     ..setup()
                 },
+                extra_channels(),
+                control_roles(),
+                log_channel(),
                 xsaid(),
                 autojoin(),
                 required_role(),
                 voice(),
                 server_voice(),
+                default_lang(),
+                response_language(),
+                allowed_voices(),
                 mode(),
                 server_mode(),
                 msg_length(),
@@ -1757,9 +2642,22 @@ pub fn commands() -> [Command; 5] {
                 speaking_rate(),
                 nick(),
                 repeated_characters(),
+                max_chars(),
+                truncation_notice(),
+                max_queue_length(),
+                queue_drop_oldest(),
                 audience_ignore(),
+                speak_responses(),
+                announce_voice(),
+                read_receipts(),
+                mention_only_ignore(),
+                transcript(),
+                transcript_opt_out(),
+                xsaid_opt_out(),
                 require_voice(),
                 required_prefix(),
+                ignore_prefix(),
+                confirm_emoji(),
                 command_prefix(),
                 block(),
             ],
@@ -18,6 +18,14 @@ pub const RED: u32 = 0xff0000;
 pub const FREE_NEUTRAL_COLOUR: u32 = 0x3498db;
 pub const PREMIUM_NEUTRAL_COLOUR: u32 = 0xcaa652;
 
+/// The most requests sent to the TTS service at once by a single task, so it is not overloaded.
+pub const TTS_SERVICE_CONCURRENCY: usize = 8;
+/// The default `msg_length`, for requests made outside of a guild.
+pub const DEFAULT_MSG_LENGTH: u16 = 30;
+
+/// The most spoken messages kept in a guild's transcript, older messages are dropped first.
+pub const MAX_TRANSCRIPT_ENTRIES: usize = 250;
+
 pub const OPTION_SEPERATORS: [&str; 4] = [
     ":small_orange_diamond:",
     ":small_blue_diamond:",
@@ -47,6 +55,8 @@ pub const DB_SETUP_QUERY: &str = "
         user_id             bigint     PRIMARY KEY,
         dm_blocked          bool       DEFAULT False,
         dm_welcomed         bool       DEFAULT false,
+        transcript_opt_out  bool       DEFAULT False,
+        xsaid_opt_out       bool       DEFAULT False,
         voice_mode          TTSMode,
         premium_voice_mode  TTSMode
     );
@@ -54,6 +64,7 @@ pub const DB_SETUP_QUERY: &str = "
     CREATE TABLE guilds (
         guild_id        bigint      PRIMARY KEY,
         channel         bigint      DEFAULT 0,
+        extra_channels  bigint[]    DEFAULT '{}',
         premium_user    bigint,
         required_role   bigint,
         xsaid           bool        DEFAULT True,
@@ -63,10 +74,27 @@ pub const DB_SETUP_QUERY: &str = "
         require_voice   bool        DEFAULT True,
         msg_length      smallint    DEFAULT 30,
         repeated_chars  smallint    DEFAULT 0,
+        max_queue_length smallint   DEFAULT 0,
+        volume          smallint    DEFAULT 100,
+        queue_drop_oldest bool      DEFAULT False,
+        max_chars       smallint    DEFAULT 0,
+        truncation_notice bool      DEFAULT True,
+        control_roles   bigint[]    DEFAULT '{}',
+        log_channel     bigint,
         prefix          varchar(6)  DEFAULT '-',
         required_prefix varchar(6),
+        ignore_prefix   varchar(6),
         target_lang     varchar(5),
+        default_lang    varchar(5),
+        language        varchar(5),
+        transcript      bool        DEFAULT False,
+        allowed_voices  text[]      DEFAULT '{}',
         audience_ignore bool        DEFAULT True,
+        speak_responses bool        DEFAULT False,
+        announce_voice  bool        DEFAULT False,
+        read_receipts   bool        DEFAULT False,
+        mention_only_ignore bool    DEFAULT False,
+        confirm_emoji   varchar(64),
         voice_mode      TTSMode     DEFAULT 'gtts',
 
         FOREIGN KEY         (premium_user)
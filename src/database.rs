@@ -119,6 +119,11 @@ where
         Ok(row)
     }
 
+    /// Gets the row only if it is already cached, for sync code which cannot query the database.
+    pub fn get_cached(&self, identifier: CacheKey) -> Option<Arc<RowT::Compacted>> {
+        self.cache.get(&identifier).map(|row| row.clone())
+    }
+
     pub async fn create_row(&self, identifier: CacheKey) -> Result<()> {
         identifier
             .bind_query(sqlx::query(self.create_row))
@@ -70,6 +70,9 @@ pub trait Compact {
 #[derive(sqlx::FromRow)]
 pub struct GuildRowRaw {
     pub channel: i64,
+    pub extra_channels: Vec<i64>,
+    pub control_roles: Vec<i64>,
+    pub log_channel: Option<i64>,
     pub premium_user: Option<i64>,
     pub required_role: Option<i64>,
     pub xsaid: bool,
@@ -78,22 +81,44 @@ pub struct GuildRowRaw {
     pub to_translate: bool,
     pub require_voice: bool,
     pub audience_ignore: bool,
+    pub speak_responses: bool,
+    pub announce_voice: bool,
+    pub read_receipts: bool,
+    pub mention_only_ignore: bool,
+    pub transcript: bool,
+    pub queue_drop_oldest: bool,
+    pub truncation_notice: bool,
     pub msg_length: i16,
     pub repeated_chars: i16,
+    pub max_queue_length: i16,
+    pub max_chars: i16,
+    pub volume: i16,
     pub prefix: String,
     pub target_lang: Option<String>,
+    pub default_lang: Option<String>,
+    pub language: Option<String>,
     pub required_prefix: Option<String>,
+    pub ignore_prefix: Option<String>,
+    pub confirm_emoji: Option<String>,
+    pub allowed_voices: Vec<String>,
     pub voice_mode: TTSMode,
 }
 
 named_bitflags! {
-    pub struct GuildRowFlags: u8 {
-        const XSAID =           0b000001;
-        const AUTO_JOIN =       0b000010;
-        const BOT_IGNORE =      0b000100;
-        const TO_TRANSLATE =    0b001000;
-        const REQUIRE_VOICE =   0b010000;
-        const AUDIENCE_IGNORE = 0b100000;
+    pub struct GuildRowFlags: u16 {
+        const XSAID =               0b0000000000000001;
+        const AUTO_JOIN =           0b0000000000000010;
+        const BOT_IGNORE =          0b0000000000000100;
+        const TO_TRANSLATE =        0b0000000000001000;
+        const REQUIRE_VOICE =       0b0000000000010000;
+        const AUDIENCE_IGNORE =     0b0000000000100000;
+        const SPEAK_RESPONSES =     0b0000000001000000;
+        const ANNOUNCE_VOICE =      0b0000000010000000;
+        const READ_RECEIPTS =       0b0000000100000000;
+        const MENTION_ONLY_IGNORE = 0b0000001000000000;
+        const TRANSCRIPT =          0b0000010000000000;
+        const QUEUE_DROP_OLDEST =   0b0000100000000000;
+        const TRUNCATION_NOTICE =   0b0001000000000000;
     }
 }
 
@@ -101,16 +126,50 @@ named_bitflags! {
 pub struct GuildRow {
     pub flags: GuildRowFlags,
     pub channel: Option<ChannelId>,
+    pub extra_channels: Vec<ChannelId>,
+    /// Roles allowed to make the bot join and leave, empty if everyone is allowed.
+    pub control_roles: Vec<RoleId>,
+    /// Where to post a record of the bot joining and leaving, off if `None`.
+    pub log_channel: Option<ChannelId>,
     pub premium_user: Option<UserId>,
     pub required_role: Option<RoleId>,
     pub msg_length: u16,
     pub repeated_chars: u16,
+    pub max_queue_length: u16,
+    pub max_chars: u16,
+    /// The volume of new messages as a percentage, from 0 to 200.
+    pub volume: u16,
     pub prefix: ArrayString<8>,
     pub target_lang: Option<ArrayString<8>>,
+    pub default_lang: Option<ArrayString<8>>,
+    /// The translation to respond in, overriding each user's locale if set.
+    pub language: Option<ArrayString<8>>,
     pub required_prefix: Option<ArrayString<8>>,
+    /// Messages starting with this are not read out, such as commands for other bots.
+    pub ignore_prefix: Option<ArrayString<8>>,
+    pub confirm_emoji: Option<ArrayString<64>>,
+    pub allowed_voices: Vec<ArrayString<MAX_VOICE_LENGTH>>,
     pub voice_mode: TTSMode,
 }
 
+impl GuildRow {
+    /// The channel set with `/setup`, followed by any added with `/set extra_channels`.
+    pub fn setup_channels(&self) -> impl Iterator<Item = ChannelId> + '_ {
+        self.channel
+            .into_iter()
+            .chain(self.extra_channels.iter().copied())
+    }
+
+    pub fn is_setup_channel(&self, channel_id: ChannelId) -> bool {
+        self.setup_channels().any(|id| id == channel_id)
+    }
+
+    /// If users are allowed to pick `voice`, an empty allowlist allows all voices.
+    pub fn is_voice_allowed(&self, voice: &str) -> bool {
+        self.allowed_voices.is_empty() || self.allowed_voices.iter().any(|v| v.as_str() == voice)
+    }
+}
+
 impl Compact for GuildRowRaw {
     type Compacted = GuildRow;
     fn compact(self) -> Self::Compacted {
@@ -121,38 +180,131 @@ impl Compact for GuildRowRaw {
         set_flag_if!(flags, GuildRowFlags::TO_TRANSLATE, self.to_translate);
         set_flag_if!(flags, GuildRowFlags::REQUIRE_VOICE, self.require_voice);
         set_flag_if!(flags, GuildRowFlags::AUDIENCE_IGNORE, self.audience_ignore);
+        set_flag_if!(flags, GuildRowFlags::SPEAK_RESPONSES, self.speak_responses);
+        set_flag_if!(flags, GuildRowFlags::ANNOUNCE_VOICE, self.announce_voice);
+        set_flag_if!(flags, GuildRowFlags::READ_RECEIPTS, self.read_receipts);
+        set_flag_if!(
+            flags,
+            GuildRowFlags::MENTION_ONLY_IGNORE,
+            self.mention_only_ignore
+        );
+        set_flag_if!(flags, GuildRowFlags::TRANSCRIPT, self.transcript);
+        set_flag_if!(
+            flags,
+            GuildRowFlags::QUEUE_DROP_OLDEST,
+            self.queue_drop_oldest
+        );
+        set_flag_if!(
+            flags,
+            GuildRowFlags::TRUNCATION_NOTICE,
+            self.truncation_notice
+        );
 
         Self::Compacted {
             flags,
             channel: (self.channel != 0).then(|| ChannelId::new(self.channel as u64)),
+            extra_channels: self
+                .extra_channels
+                .into_iter()
+                .map(|id| ChannelId::new(id as u64))
+                .collect(),
+            control_roles: self
+                .control_roles
+                .into_iter()
+                .map(|id| RoleId::new(id as u64))
+                .collect(),
+            log_channel: self.log_channel.map(|id| ChannelId::new(id as u64)),
             premium_user: self.premium_user.map(|id| UserId::new(id as u64)),
             required_role: self.required_role.map(|id| RoleId::new(id as u64)),
             msg_length: self.msg_length as u16,
             repeated_chars: self.repeated_chars as u16,
+            max_queue_length: self.max_queue_length as u16,
+            max_chars: self.max_chars as u16,
+            volume: self.volume as u16,
             prefix: truncate_convert(self.prefix, "guild.prefix"),
             target_lang: self
                 .target_lang
                 .map(|t| truncate_convert(t, "guild.target_lang")),
+            default_lang: self
+                .default_lang
+                .map(|t| truncate_convert(t, "guild.default_lang")),
+            language: self.language.map(|t| truncate_convert(t, "guild.language")),
             required_prefix: self
                 .required_prefix
                 .map(|t| truncate_convert(t, "guild.required_prefix")),
+            ignore_prefix: self
+                .ignore_prefix
+                .map(|t| truncate_convert(t, "guild.ignore_prefix")),
+            confirm_emoji: self
+                .confirm_emoji
+                .map(|e| truncate_convert(e, "guild.confirm_emoji")),
+            allowed_voices: self
+                .allowed_voices
+                .into_iter()
+                .map(|v| truncate_convert(v, "guild.allowed_voices"))
+                .collect(),
             voice_mode: self.voice_mode,
         }
     }
 }
 
+/// A guild row with the database defaults, as if the guild had never changed a setting.
+#[cfg(test)]
+pub fn test_guild_row() -> GuildRow {
+    GuildRowRaw {
+        channel: 0,
+        extra_channels: Vec::new(),
+        control_roles: Vec::new(),
+        log_channel: None,
+        premium_user: None,
+        required_role: None,
+        xsaid: true,
+        auto_join: false,
+        bot_ignore: true,
+        to_translate: false,
+        require_voice: true,
+        audience_ignore: true,
+        speak_responses: false,
+        announce_voice: false,
+        read_receipts: false,
+        mention_only_ignore: false,
+        transcript: false,
+        queue_drop_oldest: false,
+        truncation_notice: true,
+        msg_length: 30,
+        repeated_chars: 0,
+        max_queue_length: 0,
+        max_chars: 0,
+        volume: 100,
+        prefix: String::from("-"),
+        target_lang: None,
+        default_lang: None,
+        language: None,
+        required_prefix: None,
+        ignore_prefix: None,
+        confirm_emoji: None,
+        allowed_voices: Vec::new(),
+        voice_mode: TTSMode::gTTS,
+    }
+    .compact()
+}
+
 #[derive(sqlx::FromRow)]
 pub struct UserRowRaw {
     pub dm_blocked: bool,
     pub dm_welcomed: bool,
+    pub transcript_opt_out: bool,
+    pub xsaid_opt_out: bool,
     pub voice_mode: Option<TTSMode>,
     pub premium_voice_mode: Option<TTSMode>,
 }
 
 named_bitflags! {
     pub struct UserRowFlags: u8 {
-        const DM_BLOCKED =  0b01;
-        const DM_WELCOMED = 0b10;
+        const DM_BLOCKED =         0b0001;
+        const DM_WELCOMED =        0b0010;
+        const TRANSCRIPT_OPT_OUT = 0b0100;
+        const XSAID_OPT_OUT =      0b1000;
     }
 }
 
@@ -169,6 +321,12 @@ impl Compact for UserRowRaw {
         let mut flags = UserRowFlags::empty();
         set_flag_if!(flags, UserRowFlags::DM_BLOCKED, self.dm_blocked);
         set_flag_if!(flags, UserRowFlags::DM_WELCOMED, self.dm_welcomed);
+        set_flag_if!(
+            flags,
+            UserRowFlags::TRANSCRIPT_OPT_OUT,
+            self.transcript_opt_out
+        );
+        set_flag_if!(flags, UserRowFlags::XSAID_OPT_OUT, self.xsaid_opt_out);
 
         Self::Compacted {
             flags,
@@ -247,3 +405,24 @@ impl Compact for NicknameRowRaw {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn voice_allowlist() {
+        let mut guild_row = test_guild_row();
+        assert!(guild_row.is_voice_allowed("en"));
+
+        guild_row.allowed_voices = vec![
+            ArrayString::from("en").unwrap(),
+            ArrayString::from("fr-CA").unwrap(),
+        ];
+
+        assert!(guild_row.is_voice_allowed("en"));
+        assert!(guild_row.is_voice_allowed("fr-CA"));
+        assert!(!guild_row.is_voice_allowed("fr"));
+        assert!(!guild_row.is_voice_allowed("en-GB"));
+    }
+}
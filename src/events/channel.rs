@@ -21,6 +21,7 @@ pub async fn channel_delete(data: &Data, channel: &serenity::GuildChannel) -> Re
         // Ignore errors from leaving the channel, probably already left.
         let _ = data.songbird.remove(channel.guild_id).await;
         data.last_to_xsaid_tracker.remove(&channel.guild_id);
+        data.last_spoken.remove(&channel.guild_id);
     }
 
     Ok(())
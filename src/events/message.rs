@@ -1,4 +1,4 @@
-use std::{borrow::Cow, num::NonZeroU16};
+use std::{borrow::Cow, num::NonZeroU16, sync::Arc};
 
 use tracing::info;
 
@@ -8,11 +8,11 @@ use poise::serenity_prelude as serenity;
 use crate::{
     constants::DM_WELCOME_MESSAGE,
     errors,
-    funcs::{self, clean_msg, dm_generic, random_footer, run_checks},
-    opt_ext::OptionTryUnwrap,
+    funcs::{self, clean_msg, dm_generic, random_footer, run_checks, spoken_name},
+    opt_ext::{OptionGettext, OptionTryUnwrap},
     require,
-    structs::{Data, FrameworkContext, JoinVCToken, Result, TTSMode},
-    traits::SongbirdManagerExt,
+    structs::{Data, FrameworkContext, JoinVCToken, Result, TTSMode, TranscriptEntry},
+    traits::{JoinVCError, SongbirdManagerExt},
 };
 
 pub async fn message(
@@ -39,9 +39,15 @@ async fn process_tts_msg(
     let guild_id = require!(message.guild_id, Ok(()));
     let guild_row = data.guilds_db.get(guild_id.into()).await?;
 
-    let (mut content, to_autojoin) = require!(run_checks(ctx, message, &guild_row).await?, Ok(()));
+    // Read before any processing, so a clear while this message is being handled drops it too.
+    let clear_generation = data.clear_generation(guild_id);
 
-    let (voice, mode) = {
+    let (mut content, to_autojoin) = require!(
+        run_checks(ctx, message, &guild_row, &data.regex_cache).await?,
+        Ok(())
+    );
+
+    let (voice, mode, speaker, user_row) = {
         if let Some(channel_id) = to_autojoin {
             let join_vc_lock = JoinVCToken::acquire(data, guild_id);
             match data
@@ -50,7 +56,7 @@ async fn process_tts_msg(
                 .await
             {
                 Ok(call) => call,
-                Err(songbird::error::JoinError::TimedOut) => return Ok(()),
+                Err(JoinVCError::Timeout) => return Ok(()),
                 Err(err) => return Err(err.into()),
             };
         }
@@ -76,6 +82,7 @@ async fn process_tts_msg(
             .nickname_db
             .get([guild_id.into(), message.author.id.into()])
             .await?;
+        let user_row = data.userinfo_db.get(message.author.id.into()).await?;
 
         content = clean_msg(
             &content,
@@ -86,13 +93,20 @@ async fn process_tts_msg(
             &message.attachments,
             &voice,
             guild_row.flags.xsaid(),
+            user_row.flags.xsaid_opt_out(),
             guild_row.repeated_chars as usize,
             nickname_row.name.as_deref(),
+            usize::from(guild_row.max_chars),
+            guild_row.flags.truncation_notice().then(|| {
+                data.guild_catalog(&guild_row)
+                    .gettext("... message too long, truncated")
+            }),
             &data.regex_cache,
             &data.last_to_xsaid_tracker,
         );
 
-        (voice, mode)
+        let speaker = spoken_name(&message.author, member_nick, nickname_row.name.as_deref());
+        (voice, mode, speaker.to_owned(), user_row)
     };
 
     if let Some(target_lang) = guild_row.target_lang.as_deref()
@@ -143,41 +157,44 @@ async fn process_tts_msg(
             .await
         {
             Ok(call) => call,
-            Err(songbird::error::JoinError::TimedOut) => return Ok(()),
+            Err(JoinVCError::Timeout) => return Ok(()),
             Err(err) => return Err(err.into()),
         }
     };
 
-    // Pre-fetch the audio to handle max_length errors
-    let audio = require!(
-        funcs::fetch_audio(
-            &data.reqwest,
+    let track_handle = require!(
+        funcs::enqueue_audio(
+            data,
+            &call_lock,
+            guild_id,
+            message.author.id,
             url.clone(),
-            data.config.tts_service_auth_key.as_deref()
+            clear_generation,
         )
         .await?,
         Ok(())
     );
 
-    let hint = audio
-        .headers()
-        .get(reqwest::header::CONTENT_TYPE)
-        .map(|ct| {
-            let mut hint = songbird::input::core::probe::Hint::new();
-            hint.mime_type(ct.to_str()?);
-            Ok::<_, anyhow::Error>(hint)
-        })
-        .transpose()?;
-
-    let input = Box::new(std::io::Cursor::new(audio.bytes().await?));
-    let wrapped_audio =
-        songbird::input::LiveInput::Raw(songbird::input::AudioStream { input, hint });
-
-    let track_handle = {
-        let mut call = call_lock.lock().await;
-        call.enqueue_input(songbird::input::Input::Live(wrapped_audio, None))
-            .await
-    };
+    // Only recorded once it starts playing, as it may be dropped or cleared before being read out.
+    track_handle.add_event(
+        songbird::Event::Track(songbird::TrackEvent::Play),
+        LastSpokenHandler {
+            data: data.clone(),
+            guild_id,
+            url,
+        },
+    )?;
+
+    if funcs::should_log_transcript(&guild_row, &user_row) {
+        let entry = TranscriptEntry {
+            user_id: message.author.id,
+            speaker,
+            content: content.clone(),
+            time: std::time::SystemTime::now(),
+        };
+
+        funcs::log_transcript(&data.transcripts, guild_id, entry);
+    }
 
     data.analytics.log(
         Cow::Borrowed(match mode {
@@ -205,6 +222,36 @@ async fn process_tts_msg(
         ("Mode", Cow::Owned(mode.to_string()), true),
     ];
 
+    let can_add_receipts = guild_row.flags.read_receipts() && {
+        let bot_id = ctx.cache.current_user().id;
+        // Threads are not in the channel list, so let the reaction fail if missing permissions.
+        guild
+            .channels
+            .get(&message.channel_id)
+            .zip(guild.members.get(&bot_id))
+            .map_or(true, |(channel, member)| {
+                guild.user_permissions_in(channel, member).add_reactions()
+            })
+    };
+
+    drop(guild);
+    if can_add_receipts {
+        let result = message
+            .channel_id
+            .create_reaction(&ctx.http, message.id, QUEUED_REACTION)
+            .await;
+
+        log_receipt_error(result);
+        track_handle.add_event(
+            songbird::Event::Track(songbird::TrackEvent::End),
+            ReadReceiptHandler {
+                http: ctx.http.clone(),
+                channel_id: message.channel_id,
+                message_id: message.id,
+            },
+        )?;
+    }
+
     let shard_manager = framework_ctx.shard_manager.clone();
     let author_name = message.author.name.clone();
     let icon_url = message.author.face();
@@ -222,6 +269,84 @@ async fn process_tts_msg(
     .map_err(Into::into)
 }
 
+/// Added once a message has been queued, then swapped for [`READ_REACTION`] once read out.
+const QUEUED_REACTION: char = '⏳';
+const READ_REACTION: char = '🔊';
+
+fn log_receipt_error(result: serenity::Result<()>) {
+    match result {
+        // Missing ADD_REACTIONS or the message has been deleted.
+        Err(serenity::Error::Http(error))
+            if matches!(
+                error.status_code(),
+                Some(serenity::StatusCode::FORBIDDEN | serenity::StatusCode::NOT_FOUND)
+            ) =>
+        {
+            tracing::debug!("Failed to update read receipt: {error}");
+        }
+        Err(error) => tracing::warn!("Failed to update read receipt: {error}"),
+        Ok(()) => {}
+    }
+}
+
+/// Records a track as the guild's [`Data::last_spoken`] once it starts being read out, for `/replay`.
+struct LastSpokenHandler {
+    data: Data,
+    guild_id: serenity::GuildId,
+    url: reqwest::Url,
+}
+
+#[serenity::async_trait]
+impl songbird::EventHandler for LastSpokenHandler {
+    async fn act(&self, _: &songbird::EventContext<'_>) -> Option<songbird::Event> {
+        self.data
+            .last_spoken
+            .insert(self.guild_id, self.url.clone());
+        Some(songbird::Event::Cancel)
+    }
+}
+
+/// The reaction to replace [`QUEUED_REACTION`] with, once a track has stopped playing.
+fn finished_reaction(playing: Option<&songbird::tracks::PlayMode>) -> Option<char> {
+    // Tracks that were stopped by a clear, or errored, were never fully read out.
+    matches!(playing, Some(songbird::tracks::PlayMode::End)).then_some(READ_REACTION)
+}
+
+/// Reacts to the message a track was generated from, once the track has finished playing.
+struct ReadReceiptHandler {
+    http: Arc<serenity::Http>,
+    channel_id: serenity::ChannelId,
+    message_id: serenity::MessageId,
+}
+
+#[serenity::async_trait]
+impl songbird::EventHandler for ReadReceiptHandler {
+    async fn act(&self, ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
+        let result = self
+            .channel_id
+            .delete_reaction(&self.http, self.message_id, None, QUEUED_REACTION)
+            .await;
+
+        log_receipt_error(result);
+
+        let playing = match ctx {
+            songbird::EventContext::Track([(state, _)]) => Some(&state.playing),
+            _ => None,
+        };
+
+        if let Some(reaction) = finished_reaction(playing) {
+            let result = self
+                .channel_id
+                .create_reaction(&self.http, self.message_id, reaction)
+                .await;
+
+            log_receipt_error(result);
+        }
+
+        Some(songbird::Event::Cancel)
+    }
+}
+
 async fn process_mention_msg(
     ctx: &serenity::Context,
     message: &serenity::Message,
@@ -432,3 +557,19 @@ async fn process_support_response(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use songbird::tracks::PlayMode;
+
+    #[test]
+    fn finished_reaction_only_when_read_out() {
+        assert_eq!(finished_reaction(Some(&PlayMode::End)), Some(READ_REACTION));
+
+        assert_eq!(finished_reaction(Some(&PlayMode::Stop)), None);
+        assert_eq!(finished_reaction(Some(&PlayMode::Play)), None);
+        assert_eq!(finished_reaction(Some(&PlayMode::Pause)), None);
+        assert_eq!(finished_reaction(None), None);
+    }
+}
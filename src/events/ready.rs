@@ -12,6 +12,7 @@ use crate::{
     constants::FREE_NEUTRAL_COLOUR,
     looper::Looper,
     structs::{FrameworkContext, Result},
+    voice_sweeper::VoiceSweeper,
     web_updater,
 };
 
@@ -73,13 +74,16 @@ pub async fn ready(
         .await?;
 
     if last_shard && !data.fully_started.load(Ordering::SeqCst) {
-        data.fully_started.store(true, Ordering::SeqCst);
         let stats_updater = Arc::new(BotListUpdater::new(
             data.reqwest.clone(),
             ctx.cache.clone(),
+            framework_ctx.shard_manager.clone(),
             data.bot_list_tokens.clone(),
+            cfg!(debug_assertions),
         ));
 
+        drop(data.bot_list_updater.set(stats_updater.clone()));
+
         if let Some(website_info) = data.website_info.write().take() {
             let web_updater = Arc::new(web_updater::Updater {
                 patreon_service: data.config.patreon_service.clone(),
@@ -89,10 +93,20 @@ pub async fn ready(
                 config: website_info,
             });
 
-            tokio::spawn(web_updater.start());
+            tokio::spawn(web_updater.start(data.looper_health.clone()));
         }
 
-        tokio::spawn(stats_updater.start());
+        let voice_sweeper = Arc::new(VoiceSweeper {
+            data: data.clone(),
+            cache: ctx.cache.clone(),
+            interval: data.config.voice_sweep_interval,
+        });
+
+        tokio::spawn(stats_updater.start(data.looper_health.clone()));
+        tokio::spawn(voice_sweeper.start(data.looper_health.clone()));
+
+        // Only set once every looper has been spawned, so none can be skipped by an early return.
+        data.fully_started.store(true, Ordering::SeqCst);
     }
 
     Ok(())
@@ -1,16 +1,157 @@
+use std::time::{Duration, SystemTime};
+
 use poise::serenity_prelude as serenity;
 
 use crate::{
-    opt_ext::OptionTryUnwrap,
+    database::GuildRow,
+    funcs::{enqueue_tts, spoken_name},
+    opt_ext::{OptionGettext, OptionTryUnwrap},
     structs::{Data, Result},
 };
 
-/// If (on leave) the bot should also leave as it is alone
+/// Minimum time between join/leave announcements in a guild, to avoid spam during mass joins.
+const ANNOUNCE_COOLDOWN: Duration = Duration::from_secs(3);
+
 pub async fn voice_state_update(
     ctx: &serenity::Context,
     data: &Data,
     old: Option<&serenity::VoiceState>,
     new: &serenity::VoiceState,
+) -> Result<()> {
+    track_bot_channel(ctx, data, new);
+    tokio::try_join!(
+        announce_voice_change(ctx, data, old, new),
+        leave_if_alone(ctx, data, old, new),
+    )?;
+
+    Ok(())
+}
+
+/// Keeps [`Data::connected_channel`] in sync, including when the bot is moved or kicked.
+fn track_bot_channel(ctx: &serenity::Context, data: &Data, new: &serenity::VoiceState) {
+    let Some(guild_id) = new.guild_id else {
+        return;
+    };
+
+    if new.user_id != ctx.cache.current_user().id {
+        return;
+    }
+
+    match new.channel_id {
+        Some(channel_id) => data.connected_channels.insert(guild_id, channel_id),
+        None => data.connected_channels.remove(&guild_id).map(|(_, id)| id),
+    };
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VoiceAnnouncement {
+    Joined,
+    Left,
+}
+
+/// Decides if a voice state change in a guild with the bot connected to `bot_channel` should be announced.
+fn voice_announcement(
+    guild_row: &GuildRow,
+    is_bot: bool,
+    old_channel: Option<serenity::ChannelId>,
+    new_channel: Option<serenity::ChannelId>,
+    bot_channel: serenity::ChannelId,
+    last_announcement: Option<SystemTime>,
+    now: SystemTime,
+) -> Option<VoiceAnnouncement> {
+    if is_bot || !guild_row.flags.announce_voice() {
+        return None;
+    }
+
+    let announcement = if old_channel == new_channel {
+        // Mute, deafen, etc.
+        return None;
+    } else if new_channel == Some(bot_channel) {
+        VoiceAnnouncement::Joined
+    } else if old_channel == Some(bot_channel) {
+        VoiceAnnouncement::Left
+    } else {
+        return None;
+    };
+
+    if let Some(last_announcement) = last_announcement
+        && now
+            .duration_since(last_announcement)
+            .map_or(false, |elapsed| elapsed < ANNOUNCE_COOLDOWN)
+    {
+        return None;
+    }
+
+    Some(announcement)
+}
+
+/// If the guild has `announce_voice` on, says when a user joins or leaves the bot's voice channel
+async fn announce_voice_change(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&serenity::VoiceState>,
+    new: &serenity::VoiceState,
+) -> Result<()> {
+    let Some(member) = &new.member else {
+        return Ok(());
+    };
+
+    let guild_id = new.guild_id.try_unwrap()?;
+    let Some(call_lock) = data.songbird.get(guild_id) else {
+        return Ok(());
+    };
+
+    let bot_channel: serenity::ChannelId = {
+        let call = call_lock.lock().await;
+        let Some(channel) = call.current_channel() else {
+            return Ok(());
+        };
+
+        channel.0.into()
+    };
+
+    let guild_row = data.guilds_db.get(guild_id.into()).await?;
+    let now = SystemTime::now();
+    let last_announcement = data.last_voice_announcement.get(&guild_id).map(|t| *t);
+    let Some(announcement) = voice_announcement(
+        &guild_row,
+        member.user.bot,
+        old.and_then(|vs| vs.channel_id),
+        new.channel_id,
+        bot_channel,
+        last_announcement,
+        now,
+    ) else {
+        return Ok(());
+    };
+
+    data.last_voice_announcement.insert(guild_id, now);
+
+    let catalog = data.guild_catalog(&guild_row);
+    let announcement = match announcement {
+        VoiceAnnouncement::Joined => catalog.gettext("{name} joined the voice channel"),
+        VoiceAnnouncement::Left => catalog.gettext("{name} left the voice channel"),
+    };
+
+    let user = &member.user;
+    let nickname_row = data
+        .nickname_db
+        .get([guild_id.into(), user.id.into()])
+        .await?;
+
+    let name = spoken_name(user, member.nick.as_deref(), nickname_row.name.as_deref());
+    let content = announcement.replace("{name}", name);
+
+    let bot_id = ctx.cache.current_user().id;
+    enqueue_tts(data, &call_lock, &guild_row, guild_id, bot_id, &content).await
+}
+
+/// If (on leave) the bot should also leave as it is alone
+async fn leave_if_alone(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&serenity::VoiceState>,
+    new: &serenity::VoiceState,
 ) -> Result<()> {
     // User left vc
     let Some(old) = old else { return Ok(()) };
@@ -43,5 +184,121 @@ pub async fn voice_state_update(
         return Ok(());
     };
 
+    data.last_spoken.remove(&guild_id);
     data.songbird.remove(guild_id).await.map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database_models::test_guild_row;
+
+    const BOT_CHANNEL: serenity::ChannelId = serenity::ChannelId::new(1);
+    const OTHER_CHANNEL: serenity::ChannelId = serenity::ChannelId::new(2);
+
+    fn announcing_guild_row() -> GuildRow {
+        let mut guild_row = test_guild_row();
+        guild_row.flags |= crate::database::GuildRowFlags::ANNOUNCE_VOICE;
+        guild_row
+    }
+
+    fn announce(
+        guild_row: &GuildRow,
+        is_bot: bool,
+        old: Option<serenity::ChannelId>,
+        new: Option<serenity::ChannelId>,
+        last_announcement: Option<SystemTime>,
+    ) -> Option<VoiceAnnouncement> {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        voice_announcement(
+            guild_row,
+            is_bot,
+            old,
+            new,
+            BOT_CHANNEL,
+            last_announcement,
+            now,
+        )
+    }
+
+    #[test]
+    fn announces_joins_and_leaves() {
+        let guild_row = announcing_guild_row();
+        assert_eq!(
+            announce(&guild_row, false, None, Some(BOT_CHANNEL), None),
+            Some(VoiceAnnouncement::Joined)
+        );
+        assert_eq!(
+            announce(&guild_row, false, Some(BOT_CHANNEL), None, None),
+            Some(VoiceAnnouncement::Left)
+        );
+        assert_eq!(
+            announce(
+                &guild_row,
+                false,
+                Some(OTHER_CHANNEL),
+                Some(BOT_CHANNEL),
+                None
+            ),
+            Some(VoiceAnnouncement::Joined)
+        );
+    }
+
+    #[test]
+    fn ignores_other_channels_and_state_changes() {
+        let guild_row = announcing_guild_row();
+        assert_eq!(
+            announce(&guild_row, false, None, Some(OTHER_CHANNEL), None),
+            None
+        );
+        assert_eq!(
+            announce(&guild_row, false, Some(OTHER_CHANNEL), None, None),
+            None
+        );
+        assert_eq!(
+            announce(
+                &guild_row,
+                false,
+                Some(BOT_CHANNEL),
+                Some(BOT_CHANNEL),
+                None
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_bots() {
+        let guild_row = announcing_guild_row();
+        assert_eq!(
+            announce(&guild_row, true, None, Some(BOT_CHANNEL), None),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_when_disabled() {
+        assert_eq!(
+            announce(&test_guild_row(), false, None, Some(BOT_CHANNEL), None),
+            None
+        );
+    }
+
+    #[test]
+    fn respects_cooldown() {
+        let guild_row = announcing_guild_row();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+
+        let recent = Some(now - ANNOUNCE_COOLDOWN / 2);
+        assert_eq!(
+            announce(&guild_row, false, None, Some(BOT_CHANNEL), recent),
+            None
+        );
+
+        let expired = Some(now - ANNOUNCE_COOLDOWN);
+        assert_eq!(
+            announce(&guild_row, false, None, Some(BOT_CHANNEL), expired),
+            Some(VoiceAnnouncement::Joined)
+        );
+    }
+}
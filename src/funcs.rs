@@ -14,7 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{borrow::Cow, collections::BTreeMap, fmt::Write};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use itertools::Itertools as _;
 use rand::Rng as _;
@@ -23,12 +31,13 @@ use poise::serenity_prelude as serenity;
 use serenity::{builder::*, json};
 
 use crate::{
-    database::GuildRow,
+    constants::MAX_TRANSCRIPT_ENTRIES,
+    database::{GuildRow, UserRow},
     opt_ext::{OptionGettext, OptionTryUnwrap},
     require,
     structs::{
-        Context, Data, GoogleGender, GoogleVoice, LastToXsaidTracker, RegexCache, Result, TTSMode,
-        TTSServiceError,
+        Context, Data, GoogleGender, GoogleVoice, LastToXsaidTracker, QueuedMessage, RegexCache,
+        Result, TTSMode, TTSServiceError, TranscriptEntry, TranscriptLog,
     },
 };
 
@@ -101,6 +110,159 @@ pub async fn fetch_audio(
     }
 }
 
+/// Fetches the audio from the TTS service and adds it to the end of the call's queue.
+///
+/// The track is tagged with a [`QueuedMessage`], so it can be found again with
+/// [`remove_queued_from`] or fetched again with [`queued_messages`].
+///
+/// `clear_generation` is [`Data::clear_generation`] from when the message was received, so a
+/// message being processed or fetched when the queue is cleared is dropped too.
+///
+/// Returns `None` if the TTS service decided the audio should be ignored, if the
+/// queue was cleared since `clear_generation`, or if the queue is full.
+pub async fn enqueue_audio(
+    data: &Data,
+    call_lock: &tokio::sync::Mutex<songbird::Call>,
+    guild_id: serenity::GuildId,
+    author_id: serenity::UserId,
+    url: reqwest::Url,
+    clear_generation: u64,
+) -> Result<Option<songbird::tracks::TrackHandle>> {
+    let guild_row = data.guilds_db.get(guild_id.into()).await?;
+
+    // Pre-fetch the audio to handle max_length errors
+    let audio = require!(
+        fetch_audio(
+            &data.reqwest,
+            url.clone(),
+            data.config.tts_service_auth_key.as_deref()
+        )
+        .await?,
+        Ok(None)
+    );
+
+    let hint = audio
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .map(|ct| {
+            let mut hint = songbird::input::core::probe::Hint::new();
+            hint.mime_type(ct.to_str()?);
+            Ok::<_, anyhow::Error>(hint)
+        })
+        .transpose()?;
+
+    let input = Box::new(std::io::Cursor::new(audio.bytes().await?));
+    let wrapped_audio =
+        songbird::input::LiveInput::Raw(songbird::input::AudioStream { input, hint });
+
+    let mut call = call_lock.lock().await;
+    if data.clear_generation(guild_id) != clear_generation {
+        // This message was sent before the queue was cleared, so should be cleared too.
+        return Ok(None);
+    }
+
+    let queue = call.queue();
+    if guild_row.max_queue_length != 0 && queue.len() >= usize::from(guild_row.max_queue_length) {
+        data.warn_queue_overflow(guild_id);
+        if !guild_row.flags.queue_drop_oldest() {
+            return Ok(None);
+        }
+
+        // Index 0 is currently playing, so the oldest message waiting is at index 1.
+        if let Some(oldest) = queue.dequeue(1) {
+            drop(oldest.stop());
+        }
+    }
+
+    let track = songbird::tracks::Track::new_with_data(
+        songbird::input::Input::Live(wrapped_audio, None),
+        Arc::new(QueuedMessage { author_id, url }),
+    )
+    .volume(f32::from(guild_row.volume) / 100.0);
+
+    Ok(Some(call.enqueue(track).await))
+}
+
+/// The messages in the queue, including the one currently playing, in the order they will play.
+pub fn queued_messages(queue: &songbird::tracks::TrackQueue) -> Vec<Arc<QueuedMessage>> {
+    queue
+        .current_queue()
+        .iter()
+        .map(songbird::tracks::TrackHandle::data::<QueuedMessage>)
+        .collect()
+}
+
+/// Removes every queued message sent by `user_id`, returning how many were removed.
+pub fn remove_queued_from(
+    queue: &songbird::tracks::TrackQueue,
+    user_id: serenity::UserId,
+) -> usize {
+    let is_from_user =
+        |track: &songbird::tracks::TrackHandle| track.data::<QueuedMessage>().author_id == user_id;
+
+    let mut removed = queue.modify_queue(|tracks| {
+        remove_waiting(tracks, |track| {
+            let from_user = is_from_user(track);
+            if from_user {
+                drop(track.stop());
+            }
+
+            from_user
+        })
+    });
+
+    if queue.current().is_some_and(|track| is_from_user(&track)) {
+        drop(queue.skip());
+        removed += 1;
+    }
+
+    removed
+}
+
+/// Removes the tracks waiting to be played that `should_remove`, returning how many were removed.
+///
+/// Index 0 is currently playing, which has to be skipped instead of removed, so is always kept.
+fn remove_waiting<T>(
+    tracks: &mut std::collections::VecDeque<T>,
+    mut should_remove: impl FnMut(&T) -> bool,
+) -> usize {
+    let before = tracks.len();
+
+    let mut index = 0;
+    tracks.retain(|track| {
+        index += 1;
+        index == 1 || !should_remove(track)
+    });
+
+    before - tracks.len()
+}
+
+/// Says `content` in the call with the voice of `user_id`, skipping all message processing.
+pub async fn enqueue_tts(
+    data: &Data,
+    call_lock: &tokio::sync::Mutex<songbird::Call>,
+    guild_row: &GuildRow,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    content: &str,
+) -> Result<()> {
+    let clear_generation = data.clear_generation(guild_id);
+    let (voice, mode) = data.parse_user_or_guild(user_id, Some(guild_id)).await?;
+    let speaking_rate = data.speaking_rate(user_id, mode).await?;
+
+    let url = prepare_url(
+        data.config.tts_service.clone(),
+        content,
+        &voice,
+        mode,
+        &speaking_rate,
+        &guild_row.msg_length.to_string(),
+    );
+
+    enqueue_audio(data, call_lock, guild_id, user_id, url, clear_generation).await?;
+    Ok(())
+}
+
 pub fn prepare_url(
     mut tts_service: reqwest::Url,
     content: &str,
@@ -187,12 +349,42 @@ pub fn prepare_gcloud_voices(
     cleaned_map
 }
 
+pub const FOOTER_COUNT: usize = 4;
+
+/// The index of the footer last picked by [`random_footer`], shared by all guilds.
+static LAST_FOOTER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Picks a random footer, which is never the same as the last one picked.
 pub fn random_footer<'a>(
     server_invite: &str,
     client_id: serenity::UserId,
     catalog: Option<&'a gettext::Catalog>,
 ) -> Cow<'a, str> {
-    match rand::thread_rng().gen_range(0..4) {
+    let index = if FOOTER_COUNT == 1 {
+        0
+    } else {
+        let last = LAST_FOOTER.load(Ordering::Relaxed);
+        // Pick from one less footer, then skip over the last one to keep the choice uniform.
+        let index = rand::thread_rng().gen_range(0..FOOTER_COUNT - 1);
+        if index >= last {
+            index + 1
+        } else {
+            index
+        }
+    };
+
+    LAST_FOOTER.store(index, Ordering::Relaxed);
+    footer_at(index, server_invite, client_id, catalog)
+}
+
+/// Gets a specific footer, wrapping around if `index` is over [`FOOTER_COUNT`].
+pub fn footer_at<'a>(
+    index: usize,
+    server_invite: &str,
+    client_id: serenity::UserId,
+    catalog: Option<&'a gettext::Catalog>,
+) -> Cow<'a, str> {
+    match index % FOOTER_COUNT {
         0 => Cow::Owned(catalog.gettext("If you find a bug or want to ask a question, join the support server: {server_invite}").replace("{server_invite}", server_invite)),
         1 => Cow::Owned(catalog.gettext("You can vote for me or review me on top.gg!\nhttps://top.gg/bot/{client_id}").replace("{client_id}", &client_id.to_string())),
         2 => Cow::Borrowed(catalog.gettext("If you want to support the development and hosting of TTS Bot, check out `/premium`!")),
@@ -265,13 +457,33 @@ fn remove_repeated_chars(content: &str, limit: usize) -> String {
         .collect()
 }
 
+/// If a message from the user should be added to the guild's transcript, skipping those who opted out.
+pub fn should_log_transcript(guild_row: &GuildRow, user_row: &UserRow) -> bool {
+    guild_row.flags.transcript() && !user_row.flags.transcript_opt_out()
+}
+
+/// Adds a spoken message to the guild's transcript, dropping the oldest message if full.
+pub fn log_transcript(
+    transcripts: &TranscriptLog,
+    guild_id: serenity::GuildId,
+    entry: TranscriptEntry,
+) {
+    let mut transcript = transcripts.entry(guild_id).or_default();
+    if transcript.len() >= MAX_TRANSCRIPT_ENTRIES {
+        transcript.pop_front();
+    }
+
+    transcript.push_back(entry);
+}
+
 pub async fn run_checks(
     ctx: &serenity::Context,
     message: &serenity::Message,
     guild_row: &GuildRow,
+    regex_cache: &RegexCache,
 ) -> Result<Option<(String, Option<serenity::ChannelId>)>> {
     let guild_id = require!(message.guild_id, Ok(None));
-    if guild_row.channel != Some(message.channel_id) {
+    if !guild_row.is_setup_channel(message.channel_id) {
         // "Text in Voice" works by just sending messages in voice channels, so checking for it just takes
         // checking if the message's channel_id is the author's voice channel_id
         let guild = require!(message.guild(&ctx.cache), Ok(None));
@@ -299,20 +511,39 @@ pub async fn run_checks(
         }
     }
 
-    let mut content = {
+    if let Some(ignore_prefix) = &guild_row.ignore_prefix
+        && message.content.starts_with(ignore_prefix.as_str())
+    {
+        return Ok(None);
+    }
+
+    let (mut content, resolved_mentions) = {
         let Some(guild) = ctx.cache.guild(guild_id) else {
             return Ok(None);
         };
 
-        serenity::content_safe(
-            &guild,
-            &message.content,
-            &serenity::ContentSafeOptions::default()
-                .clean_here(false)
-                .clean_everyone(false)
-                .show_discriminator(false),
-            &message.mentions,
-        )
+        let options = serenity::ContentSafeOptions::default()
+            .clean_here(false)
+            .clean_everyone(false)
+            .show_discriminator(false);
+
+        let content = serenity::content_safe(&guild, &message.content, &options, &message.mentions);
+
+        // Each mention as it was resolved, so mention only messages can be found in the resolved text.
+        let resolved_mentions: Vec<_> = if guild_row.flags.mention_only_ignore() {
+            regex_cache
+                .mention
+                .find_iter(&message.content)
+                .map(|mention| {
+                    serenity::content_safe(&guild, mention.as_str(), &options, &message.mentions)
+                        .to_lowercase()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        (content, resolved_mentions)
     };
 
     if content.len() >= 1500 {
@@ -333,15 +564,33 @@ pub async fn run_checks(
         return Ok(None);
     }
 
+    // Checked before autojoin, so a message with nothing to read out does not join the channel.
+    // Attachments are still announced, so only messages with nothing at all are skipped.
+    if message.attachments.is_empty() && is_effectively_empty(&content, regex_cache) {
+        return Ok(None);
+    }
+
+    if guild_row.flags.mention_only_ignore()
+        && message.attachments.is_empty()
+        && is_mention_only(&content, &resolved_mentions)
+    {
+        return Ok(None); // Would only read out names
+    }
+
+    let bot_user_id = ctx.cache.current_user().id;
     let guild = require!(message.guild(&ctx.cache), Ok(None));
     let voice_state = guild.voice_states.get(&message.author.id);
-    let bot_voice_state = guild.voice_states.get(&ctx.cache.current_user().id);
+    let bot_voice_state = guild.voice_states.get(&bot_user_id);
 
     let mut to_autojoin = None;
     if message.author.bot {
         if guild_row.flags.bot_ignore() || bot_voice_state.is_none() {
             return Ok(None); // Is bot
         }
+
+        if guild_row.flags.speak_responses() && message.author.id == bot_user_id {
+            return Ok(None); // Command response, has already been spoken
+        }
     } else {
         // If the bot is in vc
         if let Some(vc) = bot_voice_state {
@@ -382,6 +631,76 @@ pub async fn run_checks(
     Ok(Some((content, to_autojoin)))
 }
 
+/// The name used to refer to a user in TTS, preferring the TTS Bot nickname over Discord names.
+pub fn spoken_name<'a>(
+    user: &'a serenity::User,
+    member_nick: Option<&'a str>,
+    nickname: Option<&'a str>,
+) -> &'a str {
+    first_non_blank([nickname, member_nick, user.global_name.as_deref()]).unwrap_or(&user.name)
+}
+
+/// The first of `names` which is set, skipping any that are empty or only whitespace.
+fn first_non_blank<'a>(names: impl IntoIterator<Item = Option<&'a str>>) -> Option<&'a str> {
+    names
+        .into_iter()
+        .flatten()
+        .find(|name| !name.trim().is_empty())
+}
+
+/// The member's name for an embed author, as an empty or whitespace only name breaks the embed.
+///
+/// Falls back to the username, then to "Unknown" if that is also blank.
+pub fn embed_author_name(member: &serenity::Member) -> &str {
+    embed_name_from(member.display_name(), member.user.name.as_str())
+}
+
+fn embed_name_from<'a>(display_name: &'a str, username: &'a str) -> &'a str {
+    first_non_blank([Some(display_name), Some(username)]).unwrap_or("Unknown")
+}
+
+/// Cuts `content` down to `max_chars` characters, returning if anything was removed.
+///
+/// Counts characters instead of bytes, so a multi-byte character is never split.
+pub fn truncate_chars(content: &mut String, max_chars: usize) -> bool {
+    match content.char_indices().nth(max_chars) {
+        Some((byte_index, _)) => {
+            content.truncate(byte_index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// If the `content_safe` resolved `content` is made up of only `mentions`, as resolved by
+/// `content_safe` too, which would only read out names.
+pub fn is_mention_only(content: &str, mentions: &[String]) -> bool {
+    if mentions.is_empty() {
+        return false;
+    }
+
+    let mut remaining = Cow::Borrowed(content);
+    for mention in mentions {
+        remaining = Cow::Owned(remaining.replacen(mention.as_str(), "", 1));
+    }
+
+    remaining.trim().is_empty()
+}
+
+/// If `content` has nothing to read out once custom emoji and links are removed,
+/// such as only whitespace, punctuation or emoji, which would be synthesized into silence.
+///
+/// Takes the `content_safe` output, where mentions are already names which will be read out,
+/// and must be checked before [`clean_msg`], as that adds the "<user> said" prefix.
+pub fn is_effectively_empty(content: &str, regex_cache: &RegexCache) -> bool {
+    let content = regex_cache.emoji.replace_all(content, "");
+
+    !linkify::LinkFinder::new()
+        .spans(&content)
+        .filter(|span| span.kind().is_none())
+        .any(|span| span.as_str().chars().any(char::is_alphanumeric))
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn clean_msg(
     content: &str,
@@ -394,8 +713,11 @@ pub fn clean_msg(
 
     voice: &str,
     xsaid: bool,
+    xsaid_opt_out: bool,
     repeated_limit: usize,
     nickname: Option<&str>,
+    max_chars: usize,
+    truncation_notice: Option<&str>,
 
     regex_cache: &RegexCache,
     last_to_xsaid_tracker: &LastToXsaidTracker,
@@ -436,10 +758,20 @@ pub fn clean_msg(
         (content != filtered_content, filtered_content)
     };
 
+    // Truncated before the "<user> said" prefix is added, so the limit is only on what they sent,
+    // and after cleaning, so it is not on raw mentions or emoji markup.
+    if max_chars != 0
+        && truncate_chars(&mut content, max_chars)
+        && let Some(truncation_notice) = truncation_notice
+    {
+        content.push_str(truncation_notice);
+    }
+
     // If xsaid is enabled, and the author has not been announced last (in one minute if more than 2 users in vc)
     let last_to_xsaid = last_to_xsaid_tracker.get(&guild_id);
 
     if xsaid
+        && !xsaid_opt_out
         && match last_to_xsaid.map(|i| *i) {
             None => true,
             Some((u_id, last_time)) => cache
@@ -476,10 +808,7 @@ pub fn clean_msg(
             .unwrap();
         }
 
-        let said_name = nickname
-            .or(member_nick)
-            .or(user.global_name.as_deref())
-            .unwrap_or(&user.name);
+        let said_name = spoken_name(user, member_nick, nickname);
 
         content = match attachments_to_format(attachments) {
             Some(file_format) if content.is_empty() => format!("{said_name} sent {file_format}"),
@@ -499,6 +828,7 @@ pub fn clean_msg(
         .unwrap();
     }
 
+    // Opted out users are still tracked, so the next user is announced as the speaker changed.
     if xsaid {
         last_to_xsaid_tracker.insert(guild_id, (user.id, std::time::SystemTime::now()));
     }
@@ -614,3 +944,283 @@ pub async fn confirm_dialog(
 
     confirm_dialog_wait(ctx.serenity_context(), &message, ctx.author().id).await
 }
+
+/// If the bot can react with `emoji`, as custom emojis must be from the current guild.
+pub fn is_emoji_usable(ctx: Context<'_>, emoji: &serenity::ReactionType) -> bool {
+    match emoji {
+        serenity::ReactionType::Custom { id, .. } => {
+            ctx.guild().is_some_and(|g| g.emojis.contains_key(id))
+        }
+        serenity::ReactionType::Unicode(emoji) => is_single_emoji(emoji),
+        _ => false,
+    }
+}
+
+/// Checks that the text is exactly one emoji, as Discord rejects reactions with anything else.
+///
+/// This is an approximation of emoji grapheme clusters, covering ZWJ sequences, skin tones,
+/// flags and keycaps, without pulling in the full Unicode segmentation tables.
+pub fn is_single_emoji(emoji: &str) -> bool {
+    const ZWJ: char = '\u{200D}';
+    const KEYCAP: char = '\u{20E3}';
+
+    let is_pictograph = |c: char| {
+        matches!(c as u32,
+            0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139 | 0x2194..=0x21AA
+            | 0x231A..=0x23FF | 0x24C2 | 0x25AA..=0x27BF | 0x2934 | 0x2935
+            | 0x2B05..=0x2B55 | 0x3030 | 0x303D | 0x3297 | 0x3299 | 0x1F000..=0x1FAFF
+        )
+    };
+    let is_extender = |c: char| {
+        matches!(c as u32, 0xFE0E | 0xFE0F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F)
+            || c == ZWJ
+            || c == KEYCAP
+    };
+    let is_regional_indicator = |c: char| matches!(c as u32, 0x1F1E6..=0x1F1FF);
+    let is_keycap_base = |c: char| c.is_ascii_digit() || c == '#' || c == '*';
+
+    let mut clusters = 0;
+    let mut previous = None;
+    let mut unpaired_flag = false;
+    for c in emoji.chars() {
+        if is_extender(c) {
+            if previous.is_none() {
+                return false;
+            }
+        } else if is_pictograph(c) || is_keycap_base(c) {
+            if is_keycap_base(c) && !emoji.ends_with(KEYCAP) {
+                return false;
+            }
+
+            if previous == Some(ZWJ) {
+                // Joined onto the previous emoji, such as 👩‍💻
+            } else if unpaired_flag && is_regional_indicator(c) {
+                unpaired_flag = false;
+            } else {
+                clusters += 1;
+                unpaired_flag = is_regional_indicator(c);
+            }
+        } else {
+            return false;
+        }
+
+        previous = Some(c);
+    }
+
+    clusters == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcript_opt_out() {
+        use crate::database::{GuildRowFlags, UserRowFlags};
+
+        let user_row = |flags| UserRow {
+            flags,
+            voice_mode: None,
+            premium_voice_mode: None,
+        };
+
+        let mut guild_row = crate::database_models::test_guild_row();
+        assert!(!should_log_transcript(
+            &guild_row,
+            &user_row(UserRowFlags::empty())
+        ));
+
+        guild_row.flags |= GuildRowFlags::TRANSCRIPT;
+        assert!(should_log_transcript(
+            &guild_row,
+            &user_row(UserRowFlags::empty())
+        ));
+        assert!(should_log_transcript(
+            &guild_row,
+            &user_row(UserRowFlags::XSAID_OPT_OUT)
+        ));
+        assert!(!should_log_transcript(
+            &guild_row,
+            &user_row(UserRowFlags::TRANSCRIPT_OPT_OUT)
+        ));
+    }
+
+    #[test]
+    fn transcript_drops_oldest() {
+        let transcripts = TranscriptLog::default();
+        let guild_id = serenity::GuildId::new(1);
+        let entry = |content: usize| TranscriptEntry {
+            user_id: serenity::UserId::new(2),
+            speaker: String::from("speaker"),
+            content: content.to_string(),
+            time: std::time::SystemTime::now(),
+        };
+
+        for content in 0..=MAX_TRANSCRIPT_ENTRIES {
+            log_transcript(&transcripts, guild_id, entry(content));
+        }
+
+        let transcript = transcripts.get(&guild_id).unwrap();
+        assert_eq!(transcript.len(), MAX_TRANSCRIPT_ENTRIES);
+        assert_eq!(transcript.front().unwrap().content, "1");
+        assert_eq!(
+            transcript.back().unwrap().content,
+            MAX_TRANSCRIPT_ENTRIES.to_string()
+        );
+    }
+
+    #[test]
+    fn remove_waiting_keeps_current() {
+        let mut tracks = std::collections::VecDeque::from([1, 2, 1, 3, 1]);
+        assert_eq!(remove_waiting(&mut tracks, |author| *author == 1), 2);
+        assert_eq!(tracks, [1, 2, 3]);
+
+        let mut tracks = std::collections::VecDeque::from([2, 3]);
+        assert_eq!(remove_waiting(&mut tracks, |author| *author == 1), 0);
+        assert_eq!(tracks, [2, 3]);
+
+        let mut tracks = std::collections::VecDeque::<u64>::new();
+        assert_eq!(remove_waiting(&mut tracks, |_| true), 0);
+    }
+
+    #[test]
+    fn footer_wraps_around() {
+        let client_id = serenity::UserId::new(1);
+        let footer = |index| footer_at(index, "https://discord.gg/invite", client_id, None);
+
+        assert!(footer(0).contains("https://discord.gg/invite"));
+        assert!(footer(1).contains("https://top.gg/bot/1"));
+        for index in 0..FOOTER_COUNT {
+            assert_eq!(footer(index), footer(index + FOOTER_COUNT));
+        }
+    }
+
+    #[test]
+    fn random_footer_never_repeats() {
+        let client_id = serenity::UserId::new(1);
+        let mut last = random_footer("invite", client_id, None);
+        for _ in 0..100 {
+            let footer = random_footer("invite", client_id, None);
+            assert_ne!(footer, last);
+            last = footer;
+        }
+    }
+
+    #[test]
+    fn mention_only() {
+        let mentions = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(is_mention_only("@gnome", &mentions(&["@gnome"])));
+        assert!(is_mention_only(
+            "@gnome  #general\n",
+            &mentions(&["@gnome", "#general"])
+        ));
+        assert!(is_mention_only(
+            "@gnome @gnome",
+            &mentions(&["@gnome", "@gnome"])
+        ));
+        assert!(is_mention_only(" @everyone ", &mentions(&["@everyone"])));
+
+        assert!(!is_mention_only(
+            "@gnome hello there",
+            &mentions(&["@gnome"])
+        ));
+        assert!(!is_mention_only("hi @gnome", &mentions(&["@gnome"])));
+        assert!(!is_mention_only("hello", &[]));
+        assert!(!is_mention_only("", &[]));
+    }
+
+    #[test]
+    fn blank_names_skipped() {
+        assert_eq!(first_non_blank([Some("nick"), Some("name")]), Some("nick"));
+        assert_eq!(first_non_blank([None, Some("name")]), Some("name"));
+        assert_eq!(
+            first_non_blank([Some(""), Some(" \u{3000}"), Some("name")]),
+            Some("name")
+        );
+        assert_eq!(first_non_blank([Some("  "), None]), None);
+    }
+
+    #[test]
+    fn embed_name_fallback() {
+        assert_eq!(embed_name_from("Display", "user"), "Display");
+        assert_eq!(embed_name_from("", "user"), "user");
+        assert_eq!(embed_name_from(" \t", "user"), "user");
+        assert_eq!(embed_name_from(" ", ""), "Unknown");
+    }
+
+    #[test]
+    fn truncate_multi_byte() {
+        let mut content = String::from("héllo wörld");
+        assert!(truncate_chars(&mut content, 7));
+        assert_eq!(content, "héllo w");
+
+        let mut content = String::from("😀🎉👍");
+        assert!(truncate_chars(&mut content, 2));
+        assert_eq!(content, "😀🎉");
+
+        let mut content = String::from("日本語");
+        assert!(!truncate_chars(&mut content, 3));
+        assert_eq!(content, "日本語");
+
+        assert!(!truncate_chars(&mut String::new(), 0));
+    }
+
+    #[test]
+    fn single_emoji() {
+        for emoji in ["👍", "✅", "❤️", "👍🏽", "👩‍💻", "🏳️‍🌈", "🇬🇧", "1️⃣", "#️⃣"]
+        {
+            assert!(is_single_emoji(emoji), "{emoji} should be accepted");
+        }
+
+        for text in [
+            "",
+            "hello",
+            "1",
+            "👍👍",
+            "🇬🇧🇫",
+            "👍 ",
+            "a👍",
+            "\u{FE0F}",
+            "<:pog:1>",
+        ] {
+            assert!(!is_single_emoji(text), "{text:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn effectively_empty() {
+        let regex_cache = RegexCache::new().unwrap();
+
+        assert!(is_effectively_empty("", &regex_cache));
+        assert!(is_effectively_empty("   \n ", &regex_cache));
+        assert!(is_effectively_empty("...!?", &regex_cache));
+        assert!(is_effectively_empty("😀🎉", &regex_cache));
+        assert!(is_effectively_empty("<:pog:123456789>", &regex_cache));
+        assert!(is_effectively_empty("<a:wave:123456789> !", &regex_cache));
+        assert!(is_effectively_empty(
+            "\t😀 <:pog:123456789>\n",
+            &regex_cache
+        ));
+        assert!(is_effectively_empty("https://example.com", &regex_cache));
+
+        // Mentions have been resolved by `content_safe`, so are names which will be read out.
+        assert!(!is_effectively_empty("@gnome", &regex_cache));
+        assert!(!is_effectively_empty("#general 😀", &regex_cache));
+        assert!(!is_effectively_empty("hi 😀", &regex_cache));
+        assert!(!is_effectively_empty(
+            "<:pog:123456789> that was good",
+            &regex_cache
+        ));
+        assert!(!is_effectively_empty(
+            "look https://example.com",
+            &regex_cache
+        ));
+        assert!(!is_effectively_empty("42", &regex_cache));
+    }
+}
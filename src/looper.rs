@@ -1,18 +1,63 @@
+use std::{sync::Arc, time::SystemTime};
+
+use rand::Rng as _;
+
+/// When each [`Looper`] last ran successfully, keyed by [`Looper::NAME`].
+#[derive(Default)]
+pub struct LooperHealth(dashmap::DashMap<&'static str, SystemTime>);
+
+impl LooperHealth {
+    /// Returns `None` if the looper has not succeeded since startup.
+    pub fn last_success(&self, name: &str) -> Option<SystemTime> {
+        self.0.get(name).map(|time| *time)
+    }
+
+    fn record_success(&self, name: &'static str) {
+        self.0.insert(name, SystemTime::now());
+    }
+}
+
 pub trait Looper {
     const NAME: &'static str;
     const MILLIS: u64;
 
     async fn loop_func(&self) -> anyhow::Result<()>;
-    async fn start(self: std::sync::Arc<Self>)
+
+    /// Called when [`Self::loop_func`] fails, override to handle failures differently.
+    async fn on_error(&self, err: anyhow::Error) {
+        tracing::error!("{} Error: {:?}", Self::NAME, err);
+    }
+
+    /// How often to run [`Self::loop_func`], override to configure the interval per instance.
+    fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(Self::MILLIS)
+    }
+
+    /// The longest random delay to add before each run, re-rolled every time.
+    ///
+    /// Override to spread out loopers that would otherwise all run at the same moment.
+    fn max_jitter(&self) -> std::time::Duration {
+        std::time::Duration::ZERO
+    }
+
+    async fn start(self: Arc<Self>, health: Arc<LooperHealth>)
     where
         Self: Sync,
     {
         tracing::info!("{}: Started background task", Self::NAME);
-        let mut interval = tokio::time::interval(std::time::Duration::from_millis(Self::MILLIS));
+        let mut interval = tokio::time::interval(self.interval());
         loop {
             interval.tick().await;
-            if let Err(err) = self.loop_func().await {
-                tracing::error!("{} Error: {:?}", Self::NAME, err);
+
+            let max_jitter = self.max_jitter();
+            if !max_jitter.is_zero() {
+                let jitter = rand::thread_rng().gen_range(std::time::Duration::ZERO..=max_jitter);
+                tokio::time::sleep(jitter).await;
+            }
+
+            match self.loop_func().await {
+                Ok(()) => health.record_success(Self::NAME),
+                Err(err) => self.on_error(err).await,
             }
         }
     }
@@ -67,6 +67,7 @@ mod migration;
 mod opt_ext;
 mod structs;
 mod traits;
+mod voice_sweeper;
 mod web_updater;
 
 use constants::PREMIUM_NEUTRAL_COLOUR;
@@ -256,8 +257,9 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
         },
     )?;
 
+    let looper_health = Arc::new(looper::LooperHealth::default());
     let analytics = Arc::new(analytics::Handler::new(pool.clone()));
-    tokio::spawn(analytics.clone().start());
+    tokio::spawn(analytics.clone().start(looper_health.clone()));
 
     let startup_message = webhooks
         .logs
@@ -280,21 +282,10 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
     );
 
     tracing::subscriber::set_global_default(logger.clone())?;
-    tokio::spawn(logger.0.start());
+    tokio::spawn(logger.0.start(looper_health.clone()));
 
     let token = config.main.token.take().unwrap();
-    let regex_cache = structs::RegexCache {
-        replacements: [
-            (
-                regex::Regex::new(r"\|\|(?s:.)*?\|\|")?,
-                ". spoiler avoided.",
-            ),
-            (regex::Regex::new(r"```(?s:.)*?```")?, ". code block."),
-            (regex::Regex::new(r"`(?s:.)*?`")?, ". code snippet."),
-        ],
-        id_in_brackets: regex::Regex::new(r"\((\d+)\)")?,
-        emoji: regex::Regex::new(r"<(a?):([^<>]+):\d+>")?,
-    };
+    let regex_cache = structs::RegexCache::new()?;
 
     let data = Data(Arc::new(DataInner {
         pool,
@@ -309,6 +300,14 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
         join_vc_tokens: dashmap::DashMap::new(),
         currently_purging: AtomicBool::new(false),
         last_to_xsaid_tracker: dashmap::DashMap::new(),
+        last_voice_announcement: dashmap::DashMap::new(),
+        transcripts: dashmap::DashMap::new(),
+        clear_generations: dashmap::DashMap::new(),
+        queue_overflow_warnings: dashmap::DashMap::new(),
+        connected_channels: dashmap::DashMap::new(),
+        command_outcomes: dashmap::DashMap::new(),
+        last_spoken: dashmap::DashMap::new(),
+        bot_list_updater: std::sync::OnceLock::new(),
 
         gtts_voices,
         espeak_voices,
@@ -321,6 +320,7 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
         reqwest,
         premium_avatar_url,
         analytics,
+        looper_health,
         webhooks,
         start_time,
         startup_message,
@@ -355,6 +355,13 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
         ),
         pre_command: |ctx| {
             Box::pin(async move {
+                // Caches the guild row, so `current_catalog` can read the guild's language.
+                if let Some(guild_id) = ctx.guild_id()
+                    && let Err(err) = ctx.data().guilds_db.get(guild_id.into()).await
+                {
+                    tracing::warn!("Failed to fetch guild row for {guild_id}: {err:?}");
+                }
+
                 let analytics_handler: &analytics::Handler = &ctx.data().analytics;
 
                 analytics_handler.log(Cow::Owned(ctx.command().qualified_name.clone()), true);
@@ -429,10 +436,12 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
         ..poise::FrameworkOptions::default()
     };
 
+    // Kept to leave all voice channels on shutdown.
+    let framework_data = data.clone();
     let mut client = serenity::Client::builder(token, intents)
         .voice_manager::<songbird::Songbird>(songbird)
         .framework(poise::Framework::new(framework_options, |_, _, _| {
-            Box::pin(async { Ok(data) })
+            Box::pin(async { Ok(framework_data) })
         }))
         .await?;
 
@@ -469,6 +478,7 @@ async fn _main(start_time: std::time::SystemTime) -> Result<()> {
         }
 
         warn!("Recieved control C and shutting down.");
+        data.leave_all_calls().await;
         shard_manager.shutdown_all().await;
     });
 
@@ -197,7 +197,9 @@ async fn _run(
 
         ALTER TABLE userinfo
             ADD COLUMN IF NOT EXISTS voice_mode            TTSMode,
-            ADD COLUMN IF NOT EXISTS premium_voice_mode    TTSMode;
+            ADD COLUMN IF NOT EXISTS premium_voice_mode    TTSMode,
+            ADD COLUMN IF NOT EXISTS transcript_opt_out    bool       DEFAULT False,
+            ADD COLUMN IF NOT EXISTS xsaid_opt_out         bool       DEFAULT False;
         ALTER TABLE guilds
             ADD COLUMN IF NOT EXISTS audience_ignore  bool       DEFAULT True,
             ADD COLUMN IF NOT EXISTS voice_mode       TTSMode    DEFAULT 'gtts',
@@ -206,7 +208,25 @@ async fn _run(
             ADD COLUMN IF NOT EXISTS premium_user     bigint,
             ADD COLUMN IF NOT EXISTS require_voice    bool       DEFAULT True,
             ADD COLUMN IF NOT EXISTS required_role    bigint,
-            ADD COLUMN IF NOT EXISTS required_prefix  varchar(6);
+            ADD COLUMN IF NOT EXISTS required_prefix  varchar(6),
+            ADD COLUMN IF NOT EXISTS speak_responses  bool       DEFAULT False,
+            ADD COLUMN IF NOT EXISTS announce_voice   bool       DEFAULT False,
+            ADD COLUMN IF NOT EXISTS allowed_voices   text[]     DEFAULT '{}',
+            ADD COLUMN IF NOT EXISTS read_receipts    bool       DEFAULT False,
+            ADD COLUMN IF NOT EXISTS mention_only_ignore bool    DEFAULT False,
+            ADD COLUMN IF NOT EXISTS default_lang     varchar(5),
+            ADD COLUMN IF NOT EXISTS transcript       bool       DEFAULT False,
+            ADD COLUMN IF NOT EXISTS confirm_emoji    varchar(64),
+            ADD COLUMN IF NOT EXISTS extra_channels   bigint[]   DEFAULT '{}',
+            ADD COLUMN IF NOT EXISTS max_queue_length smallint   DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS queue_drop_oldest bool      DEFAULT False,
+            ADD COLUMN IF NOT EXISTS max_chars        smallint   DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS truncation_notice bool      DEFAULT True,
+            ADD COLUMN IF NOT EXISTS control_roles    bigint[]   DEFAULT '{}',
+            ADD COLUMN IF NOT EXISTS ignore_prefix    varchar(6),
+            ADD COLUMN IF NOT EXISTS log_channel      bigint,
+            ADD COLUMN IF NOT EXISTS volume           smallint   DEFAULT 100,
+            ADD COLUMN IF NOT EXISTS language         varchar(5);
         ALTER TABLE user_voice
             ADD COLUMN IF NOT EXISTS speaking_rate real;
 
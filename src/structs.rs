@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, VecDeque},
     sync::Arc,
 };
 
@@ -15,6 +15,11 @@ use poise::serenity_prelude::{self as serenity, json};
 
 use crate::{analytics, database, into_static_display};
 
+/// How long [`Data::leave_all_calls`] waits for every call to disconnect.
+const LEAVE_ALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// How often to log that a guild's queue is full.
+const QUEUE_OVERFLOW_WARNING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 #[derive(serde::Deserialize)]
 pub struct Config {
     #[serde(rename = "Main")]
@@ -43,6 +48,8 @@ pub struct MainConfig {
     pub tts_service: reqwest::Url,
     pub token: Option<String>,
     pub log_level: String,
+    /// How often to disconnect orphaned voice connections in minutes, defaults to every 15 minutes.
+    pub voice_sweep_interval: Option<std::num::NonZeroU64>,
 
     // Only for situations where gTTS has broken
     #[serde(default)]
@@ -76,6 +83,14 @@ pub struct BotListTokens {
     pub top_gg: Option<String>,
     pub discord_bots_gg: Option<String>,
     pub bots_on_discord: Option<String>,
+    pub discords_com: Option<String>,
+    /// How often to update the bot lists in minutes, defaults to hourly.
+    pub update_interval: Option<std::num::NonZeroU64>,
+    /// A URL to POST the guild and shard counts to after each update, for external stats.
+    pub stats_webhook: Option<reqwest::Url>,
+    /// Disables the random delay before each update, for deterministic testing.
+    #[serde(default)]
+    pub disable_jitter: bool,
 }
 
 pub struct WebhookConfig {
@@ -84,10 +99,22 @@ pub struct WebhookConfig {
     pub errors: Option<serenity::Webhook>,
 }
 
+/// Serialises joining a guild's voice channel.
+///
+/// Tokio's mutex is fair, so concurrent joins get the token in the order they asked for it.
+/// Joins should check the bot's channel again once they have the token, as a join ahead of
+/// them in the queue may have already moved the bot.
 pub struct JoinVCToken(pub serenity::GuildId);
 impl JoinVCToken {
     pub fn acquire(data: &Data, guild_id: serenity::GuildId) -> Arc<tokio::sync::Mutex<Self>> {
-        data.join_vc_tokens
+        Self::acquire_from(&data.join_vc_tokens, guild_id)
+    }
+
+    fn acquire_from(
+        tokens: &dashmap::DashMap<serenity::GuildId, Arc<tokio::sync::Mutex<Self>>>,
+        guild_id: serenity::GuildId,
+    ) -> Arc<tokio::sync::Mutex<Self>> {
+        tokens
             .entry(guild_id)
             .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(Self(guild_id))))
             .clone()
@@ -110,6 +137,25 @@ pub struct RegexCache {
     pub replacements: [(regex::Regex, &'static str); 3],
     pub id_in_brackets: regex::Regex,
     pub emoji: regex::Regex,
+    pub mention: regex::Regex,
+}
+
+impl RegexCache {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            replacements: [
+                (
+                    regex::Regex::new(r"\|\|(?s:.)*?\|\|")?,
+                    ". spoiler avoided.",
+                ),
+                (regex::Regex::new(r"```(?s:.)*?```")?, ". code block."),
+                (regex::Regex::new(r"`(?s:.)*?`")?, ". code snippet."),
+            ],
+            id_in_brackets: regex::Regex::new(r"\((\d+)\)")?,
+            emoji: regex::Regex::new(r"<(a?):([^<>]+):\d+>")?,
+            mention: regex::Regex::new(r"<(?:@[!&]?|#)\d+>|@everyone|@here")?,
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -125,6 +171,7 @@ impl std::ops::Deref for Data {
 
 pub struct DataInner {
     pub analytics: Arc<analytics::Handler>,
+    pub looper_health: Arc<crate::looper::LooperHealth>,
     pub guilds_db: database::Handler<i64, database::GuildRowRaw>,
     pub userinfo_db: database::Handler<i64, database::UserRowRaw>,
     pub nickname_db: database::Handler<[i64; 2], database::NicknameRowRaw>,
@@ -137,6 +184,20 @@ pub struct DataInner {
     pub translations: HashMap<String, gettext::Catalog>,
     pub fully_started: std::sync::atomic::AtomicBool,
     pub last_to_xsaid_tracker: LastToXsaidTracker,
+    pub last_voice_announcement: dashmap::DashMap<serenity::GuildId, std::time::SystemTime>,
+    pub transcripts: TranscriptLog,
+    /// How many times each guild's queue has been cleared, see [`Data::clear_queue`].
+    pub clear_generations: dashmap::DashMap<serenity::GuildId, u64>,
+    /// When each guild was last warned about a full queue, see [`Data::warn_queue_overflow`].
+    pub queue_overflow_warnings: dashmap::DashMap<serenity::GuildId, std::time::Instant>,
+    /// The channel the bot is connected to in each guild, see [`Data::connected_channel`].
+    pub connected_channels: dashmap::DashMap<serenity::GuildId, serenity::ChannelId>,
+    /// How many times each voice command ended with each outcome, see [`Data::record_outcome`].
+    pub command_outcomes: dashmap::DashMap<(&'static str, &'static str), u64>,
+    /// The TTS service URL of the last message read out in each guild, for `/replay`.
+    pub last_spoken: dashmap::DashMap<serenity::GuildId, reqwest::Url>,
+    /// Set once all shards are ready, for triggering an update with `update_bot_lists`.
+    pub bot_list_updater: std::sync::OnceLock<Arc<crate::bot_list_updater::BotListUpdater>>,
     pub website_info: RwLock<Option<WebsiteInfo>>,
     pub startup_message: serenity::MessageId,
     pub start_time: std::time::SystemTime,
@@ -170,6 +231,96 @@ impl Data {
         self.translations.get("en-US")
     }
 
+    /// The catalog for the guild's configured language, for messages sent outside of a command.
+    pub fn guild_catalog(&self, guild_row: &database::GuildRow) -> Option<&gettext::Catalog> {
+        let language = guild_row.language.as_deref()?;
+        self.translations.get(language)
+    }
+
+    pub fn clear_generation(&self, guild_id: serenity::GuildId) -> u64 {
+        self.clear_generations.get(&guild_id).map_or(0, |g| *g)
+    }
+
+    /// Stops the call's queue, returning how many tracks were cleared.
+    ///
+    /// The call must be locked, as messages which started being fetched before the clear
+    /// check the generation under the same lock and are dropped instead of being queued.
+    pub fn clear_queue(&self, guild_id: serenity::GuildId, call: &songbird::Call) -> usize {
+        *self.clear_generations.entry(guild_id).or_default() += 1;
+
+        let queue = call.queue();
+        let cleared_count = queue.len();
+        queue.stop();
+        cleared_count
+    }
+
+    /// The voice channel the bot is in, without locking the guild's call.
+    ///
+    /// Kept up to date by the bot's own voice state updates, so may briefly lag behind
+    /// [`songbird::Call::current_channel`] while joining or leaving.
+    pub fn connected_channel(&self, guild_id: serenity::GuildId) -> Option<serenity::ChannelId> {
+        self.connected_channels
+            .get(&guild_id)
+            .map(|channel_id| *channel_id)
+    }
+
+    /// Counts a voice command finishing with `outcome`, such as `("join", "timeout")`.
+    pub fn record_outcome(&self, command: &'static str, outcome: &'static str) {
+        *self.command_outcomes.entry((command, outcome)).or_insert(0) += 1;
+    }
+
+    /// Every outcome counted since startup, sorted by command then outcome.
+    pub fn outcome_counts(&self) -> Vec<(&'static str, &'static str, u64)> {
+        let mut counts: Vec<_> = self
+            .command_outcomes
+            .iter()
+            .map(|entry| (entry.key().0, entry.key().1, *entry.value()))
+            .collect();
+
+        counts.sort_unstable();
+        counts
+    }
+
+    /// Logs that a guild's queue is full, at most once per [`QUEUE_OVERFLOW_WARNING_INTERVAL`].
+    pub fn warn_queue_overflow(&self, guild_id: serenity::GuildId) {
+        let now = std::time::Instant::now();
+        let mut last_warning = self.queue_overflow_warnings.entry(guild_id).or_insert(now);
+        if *last_warning != now && now - *last_warning < QUEUE_OVERFLOW_WARNING_INTERVAL {
+            return;
+        }
+
+        *last_warning = now;
+        warn!("Queue for {guild_id} is full, messages are being dropped");
+    }
+
+    /// Leaves every voice channel, so the bot is not left in them until Discord times out.
+    ///
+    /// Any calls still disconnecting after [`LEAVE_ALL_TIMEOUT`] are abandoned, so this cannot hang.
+    pub async fn leave_all_calls(&self) {
+        let mut leaves = tokio::task::JoinSet::new();
+        for (guild_id, _) in self.songbird.iter() {
+            let songbird = self.songbird.clone();
+            leaves.spawn(async move { (guild_id, songbird.remove(guild_id).await) });
+        }
+
+        let drain = async {
+            while let Some(result) = leaves.join_next().await {
+                if let Ok((guild_id, Err(err))) = result {
+                    warn!("Failed to leave {guild_id:?} during shutdown: {err:?}");
+                }
+            }
+        };
+
+        if tokio::time::timeout(LEAVE_ALL_TIMEOUT, drain)
+            .await
+            .is_err()
+        {
+            warn!("Timed out leaving voice channels during shutdown!");
+        }
+
+        self.last_to_xsaid_tracker.clear();
+    }
+
     pub async fn speaking_rate(
         &self,
         user_id: serenity::UserId,
@@ -226,6 +377,11 @@ impl Data {
         }
     }
 
+    /// Gets the voice and mode to read `author_id`'s messages in.
+    ///
+    /// Users pick a voice with `/set voice`, which is validated against the voices of their
+    /// current mode and stored in `user_voice` keyed by `(user_id, mode)`. This is read back
+    /// here, falling back to the guild's voice, and is passed to `prepare_url` for the request.
     pub async fn parse_user_or_guild(
         &self,
         author_id: serenity::UserId,
@@ -294,10 +450,77 @@ impl Data {
                 }
             } else {
                 None
-            }.unwrap_or_else(|| Cow::Borrowed(mode.default_voice()));
+            };
+
+        let voice = match voice {
+            Some(voice) => voice,
+            None => self.guild_default_voice(guild_id, mode).await?,
+        };
 
         Ok((voice, mode))
     }
+
+    /// The voice to use if no voice has been set, following the guild's `default_lang` if set.
+    async fn guild_default_voice(
+        &self,
+        guild_id: Option<serenity::GuildId>,
+        mode: TTSMode,
+    ) -> Result<Cow<'static, str>> {
+        if let Some(guild_id) = guild_id
+            && let Some(lang) = self.guilds_db.get(guild_id.into()).await?.default_lang
+            && let Some(voice) = self.voice_for_language(mode, &lang)
+        {
+            return Ok(Cow::Owned(voice));
+        }
+
+        Ok(Cow::Borrowed(mode.default_voice()))
+    }
+
+    /// Picks the first voice of `mode` which speaks `lang`, preferring an exact match
+    /// such as `en-GB` over a voice of the same base language, such as `en-US`.
+    pub fn voice_for_language(&self, mode: TTSMode, lang: &str) -> Option<String> {
+        match mode {
+            TTSMode::gTTS => find_language(self.gtts_voices.keys(), lang, |v| v.as_str()).cloned(),
+            TTSMode::eSpeak => self
+                .espeak_voices
+                .iter()
+                .find(|v| {
+                    v.trim_end_matches(|c: char| c.is_ascii_digit())
+                        .eq_ignore_ascii_case(base_language(lang))
+                })
+                .cloned(),
+            TTSMode::Polly => find_language(self.polly_voices.values(), lang, |v| {
+                v.language_code.as_str()
+            })
+            .map(|v| v.id.clone()),
+            TTSMode::gCloud => find_language(self.gcloud_voices.iter(), lang, |(l, _)| l.as_str())
+                .and_then(|(l, variants)| {
+                    let variant = variants.keys().next()?;
+                    Some(format!("{l} {variant}"))
+                }),
+        }
+    }
+}
+
+/// The language without a region, such as `en` for `en-GB`.
+fn base_language(lang: &str) -> &str {
+    lang.split('-').next().unwrap_or(lang)
+}
+
+/// Finds the first item in `lang`, or failing that, the first in the same base language.
+fn find_language<T>(
+    mut items: impl Iterator<Item = T> + Clone,
+    lang: &str,
+    item_lang: impl Fn(&T) -> &str,
+) -> Option<T> {
+    let base_lang = base_language(lang);
+
+    items
+        .clone()
+        .find(|item| item_lang(item).eq_ignore_ascii_case(lang))
+        .or_else(|| {
+            items.find(|item| base_language(item_lang(item)).eq_ignore_ascii_case(base_lang))
+        })
 }
 
 #[derive(Clone, Copy)]
@@ -499,3 +722,64 @@ pub type CommandResult<E = Error> = Result<(), E>;
 pub type FrameworkContext<'a> = poise::FrameworkContext<'a, Data, CommandError>;
 pub type LastToXsaidTracker =
     dashmap::DashMap<serenity::GuildId, (serenity::UserId, std::time::SystemTime)>;
+pub type TranscriptLog = dashmap::DashMap<serenity::GuildId, VecDeque<TranscriptEntry>>;
+
+/// Attached to each track in the queue, so it can be found or fetched again.
+pub struct QueuedMessage {
+    pub author_id: serenity::UserId,
+    pub url: reqwest::Url,
+}
+
+impl QueuedMessage {
+    /// The text being read out, taken from the TTS service URL so it is not stored twice.
+    pub fn text(&self) -> Option<Cow<'_, str>> {
+        self.url
+            .query_pairs()
+            .find_map(|(key, value)| (key == "text").then_some(value))
+    }
+}
+
+pub struct TranscriptEntry {
+    pub user_id: serenity::UserId,
+    pub speaker: String,
+    pub content: String,
+    pub time: std::time::SystemTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_matching() {
+        let voices = ["de", "en-AU", "en-GB", "fr-CA"];
+        let find = |lang| find_language(voices.iter(), lang, |v| **v).copied();
+
+        assert_eq!(find("en-GB"), Some("en-GB"));
+        assert_eq!(find("EN-gb"), Some("en-GB"));
+        assert_eq!(find("en-US"), Some("en-AU"));
+        assert_eq!(find("en"), Some("en-AU"));
+        assert_eq!(find("de-AT"), Some("de"));
+        assert_eq!(find("fr"), Some("fr-CA"));
+        assert_eq!(find("ja-JP"), None);
+    }
+
+    #[test]
+    fn join_vc_token_is_exclusive_per_guild() {
+        let tokens = dashmap::DashMap::new();
+        let guild_id = serenity::GuildId::new(1);
+
+        let first = JoinVCToken::acquire_from(&tokens, guild_id);
+        let second = JoinVCToken::acquire_from(&tokens, guild_id);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let guard = first.try_lock().unwrap();
+        assert!(second.try_lock().is_err());
+
+        let other_guild = JoinVCToken::acquire_from(&tokens, serenity::GuildId::new(2));
+        assert!(other_guild.try_lock().is_ok());
+
+        drop(guard);
+        assert_eq!(second.try_lock().unwrap().0, guild_id);
+    }
+}
@@ -1,10 +1,11 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use poise::serenity_prelude as serenity;
 
 use crate::{
     constants,
     constants::{FREE_NEUTRAL_COLOUR, PREMIUM_NEUTRAL_COLOUR},
+    funcs,
     opt_ext::{OptionGettext, OptionTryUnwrap},
     require_guild,
     structs::{Context, JoinVCToken, Result, TTSMode},
@@ -16,6 +17,8 @@ pub trait PoiseContextExt {
     fn current_catalog(&self) -> Option<&gettext::Catalog>;
     async fn send_ephemeral(&self, message: impl Into<String>) -> Result<poise::ReplyHandle<'_>>;
     async fn send_error(&self, error_message: String) -> Result<Option<poise::ReplyHandle<'_>>>;
+    async fn speak_response(&self, content: &str) -> Result<()>;
+    async fn send_not_connected(&self) -> Result<()>;
 
     async fn neutral_colour(&self) -> u32;
     fn author_vc(&self) -> Option<serenity::ChannelId>;
@@ -49,8 +52,18 @@ impl PoiseContextExt for Context<'_> {
     }
 
     fn current_catalog(&self) -> Option<&gettext::Catalog> {
+        // The guild row is fetched before every command, so is only missing if that failed.
+        let data = self.data();
+        if let Some(guild_id) = self.guild_id()
+            && let Some(guild_row) = data.guilds_db.get_cached(guild_id.into())
+            && let Some(catalog) = data.guild_catalog(&guild_row)
+        {
+            return Some(catalog);
+        }
+
         if let poise::Context::Application(ctx) = self {
-            ctx.data
+            return ctx
+                .data
                 .translations
                 .get(match ctx.interaction.locale.as_str() {
                     "ko" => "ko-KR",
@@ -91,6 +104,34 @@ impl PoiseContextExt for Context<'_> {
         Ok(handle)
     }
 
+    /// Says a command response in the voice channel, if the guild has enabled `speak_responses`.
+    async fn speak_response(&self, content: &str) -> Result<()> {
+        let Some(guild_id) = self.guild_id() else {
+            return Ok(());
+        };
+
+        let data = self.data();
+        let guild_row = data.guilds_db.get(guild_id.into()).await?;
+        if !guild_row.flags.speak_responses() {
+            return Ok(());
+        }
+
+        // Only speak if already connected, a command response should never cause a join.
+        let Some(call_lock) = data.songbird.get(guild_id) else {
+            return Ok(());
+        };
+
+        let author_id = self.author().id;
+        funcs::enqueue_tts(data, &call_lock, &guild_row, guild_id, author_id, content).await
+    }
+
+    /// Tells the user that a command needs the bot to be in a voice channel first.
+    async fn send_not_connected(&self) -> Result<()> {
+        self.say(self.gettext("**Error**: I am not in a voice channel!"))
+            .await?;
+        Ok(())
+    }
+
     async fn send_error(&self, error_message: String) -> Result<Option<poise::ReplyHandle<'_>>> {
         let author = self.author();
         let serenity_ctx = self.serenity_context();
@@ -115,7 +156,7 @@ impl PoiseContextExt for Context<'_> {
                 match channel.guild_id.member(serenity_ctx, author.id).await {
                     Ok(member) => {
                         m = member;
-                        (m.display_name(), m.face())
+                        (funcs::embed_author_name(&m), m.face())
                     }
                     Err(_) => (author.name.as_str(), author.face()),
                 }
@@ -146,12 +187,126 @@ impl PoiseContextExt for Context<'_> {
     }
 }
 
+/// The parts of a command context that checks respond through, so they can be tested with a fake.
+pub trait CheckResponder {
+    fn channel_id(&self) -> serenity::ChannelId;
+    fn translate<'a>(&'a self, translate: &'a str) -> &'a str;
+    async fn respond_error(&self, error_message: String) -> Result<()>;
+}
+
+impl CheckResponder for Context<'_> {
+    fn channel_id(&self) -> serenity::ChannelId {
+        poise::Context::channel_id(*self)
+    }
+
+    fn translate<'a>(&'a self, translate: &'a str) -> &'a str {
+        self.gettext(translate)
+    }
+
+    async fn respond_error(&self, error_message: String) -> Result<()> {
+        self.send_error(error_message).await?;
+        Ok(())
+    }
+}
+
+/// Where a command looks up its guild's channels, so lookups can be tested with a fake.
+pub trait ChannelSource {
+    /// The ids of the guild's channels, or `None` if the guild is not cached yet.
+    fn cached_channel_ids(&self) -> Option<Vec<serenity::ChannelId>>;
+    async fn fetch_channel_ids(&self) -> Result<Vec<serenity::ChannelId>>;
+}
+
+impl ChannelSource for Context<'_> {
+    fn cached_channel_ids(&self) -> Option<Vec<serenity::ChannelId>> {
+        Some(self.guild()?.channels.keys().copied().collect())
+    }
+
+    async fn fetch_channel_ids(&self) -> Result<Vec<serenity::ChannelId>> {
+        let guild_id = self.guild_id().try_unwrap()?;
+        tracing::warn!(
+            "Guild {guild_id} not cached in {} command, falling back to HTTP!",
+            self.command().qualified_name
+        );
+
+        let channels = guild_id.channels(self.http()).await?;
+        Ok(channels.into_keys().collect())
+    }
+}
+
+/// The ways joining a voice channel can fail that users should be told about.
+#[derive(Debug)]
+pub enum JoinVCError {
+    Timeout,
+    Dropped,
+    MissingPermissions {
+        missing: serenity::Permissions,
+        /// If the bot's roles have the permissions, but the channel's overwrites deny them.
+        denied_by_overwrites: bool,
+    },
+    ChannelFull,
+    /// The channel was deleted between reading the voice state and fetching it.
+    ChannelDeleted,
+    Other(songbird::error::JoinError),
+}
+
+impl From<songbird::error::JoinError> for JoinVCError {
+    fn from(err: songbird::error::JoinError) -> Self {
+        match err {
+            songbird::error::JoinError::TimedOut => Self::Timeout,
+            songbird::error::JoinError::Dropped => Self::Dropped,
+            err => Self::Other(err),
+        }
+    }
+}
+
+impl std::fmt::Display for JoinVCError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("Timed out joining voice channel"),
+            Self::Dropped => f.write_str("Connection dropped while joining voice channel"),
+            Self::MissingPermissions { missing, .. } => write!(f, "Missing permissions: {missing}"),
+            Self::ChannelFull => f.write_str("Voice channel is full"),
+            Self::ChannelDeleted => f.write_str("Voice channel no longer exists"),
+            Self::Other(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for JoinVCError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Other(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// How long to wait for a voice connection before giving up, so a hung join
+/// does not hold the guild's [`JoinVCToken`] forever.
+const JOIN_VC_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Waits for `join` to connect to `channel_id`, giving up as timed out after `timeout`.
+async fn join_within<T>(
+    timeout: Duration,
+    token: &JoinVCToken,
+    channel_id: serenity::ChannelId,
+    join: impl Future<Output = Result<T, songbird::error::JoinError>>,
+) -> Result<T, songbird::error::JoinError> {
+    match tokio::time::timeout(timeout, join).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!("Joining {channel_id} in {} hung, giving up", token.0);
+            Err(songbird::error::JoinError::TimedOut)
+        }
+    }
+}
+
 pub trait SongbirdManagerExt {
     async fn join_vc(
         &self,
         guild_id: tokio::sync::MutexGuard<'_, JoinVCToken>,
         channel_id: serenity::ChannelId,
-    ) -> Result<Arc<tokio::sync::Mutex<songbird::Call>>, songbird::error::JoinError>;
+    ) -> Result<Arc<tokio::sync::Mutex<songbird::Call>>, JoinVCError>;
 }
 
 impl SongbirdManagerExt for songbird::Songbird {
@@ -159,15 +314,76 @@ impl SongbirdManagerExt for songbird::Songbird {
         &self,
         guild_id: tokio::sync::MutexGuard<'_, JoinVCToken>,
         channel_id: serenity::ChannelId,
-    ) -> Result<Arc<tokio::sync::Mutex<songbird::Call>>, songbird::error::JoinError> {
-        match self.join(guild_id.0, channel_id).await {
+    ) -> Result<Arc<tokio::sync::Mutex<songbird::Call>>, JoinVCError> {
+        let join = self.join(guild_id.0, channel_id);
+        match join_within(JOIN_VC_TIMEOUT, &guild_id, channel_id, join).await {
             Ok(call) => Ok(call),
             Err(err) => {
                 // On error, the Call is left in a semi-connected state.
                 // We need to correct this by removing the call from the manager.
                 drop(self.leave(guild_id.0).await);
-                Err(err)
+                Err(err.into())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn hung_join_gives_up_and_releases_token() {
+        let guild_id = serenity::GuildId::new(1);
+        let channel_id = serenity::ChannelId::new(2);
+        let token = tokio::sync::Mutex::new(JoinVCToken(guild_id));
+
+        let hung_join = async {
+            let token = token.lock().await;
+            let join = std::future::pending::<Result<(), songbird::error::JoinError>>();
+            join_within(Duration::from_millis(10), &token, channel_id, join).await
+        };
+
+        // Queued behind the hung join, so only gets the token once it has given up.
+        let next_join = async {
+            tokio::task::yield_now().await;
+            token.lock().await.0
+        };
+
+        let (result, next_guild_id) = block_on(async { tokio::join!(hung_join, next_join) });
+        assert!(matches!(result, Err(songbird::error::JoinError::TimedOut)));
+        assert_eq!(next_guild_id, guild_id);
+    }
+
+    #[test]
+    fn join_within_passes_through_result() {
+        let token = JoinVCToken(serenity::GuildId::new(1));
+        let channel_id = serenity::ChannelId::new(2);
+
+        let join = async { Ok::<_, songbird::error::JoinError>(()) };
+        let result = block_on(join_within(
+            Duration::from_secs(1),
+            &token,
+            channel_id,
+            join,
+        ));
+        assert!(result.is_ok());
+
+        let join = async { Err::<(), _>(songbird::error::JoinError::Dropped) };
+        let result = block_on(join_within(
+            Duration::from_secs(1),
+            &token,
+            channel_id,
+            join,
+        ));
+        assert!(matches!(result, Err(songbird::error::JoinError::Dropped)));
+    }
+}
@@ -0,0 +1,91 @@
+use std::{num::NonZeroU64, sync::Arc, time::Duration};
+
+use poise::serenity_prelude as serenity;
+
+use crate::{
+    structs::{Data, JoinVCToken},
+    Result,
+};
+
+/// Disconnects voice connections that have been left behind, such as the bot being alone,
+/// the channel being deleted, or the call having no channel at all.
+pub struct VoiceSweeper {
+    pub data: Data,
+    pub cache: Arc<serenity::Cache>,
+    /// How often to sweep in minutes, defaults to every 15 minutes.
+    pub interval: Option<NonZeroU64>,
+}
+
+impl VoiceSweeper {
+    /// Why the call should be disconnected, or `None` if it is fine.
+    fn orphan_reason(
+        &self,
+        guild_id: serenity::GuildId,
+        channel_id: Option<serenity::ChannelId>,
+    ) -> Option<&'static str> {
+        let Some(channel_id) = channel_id else {
+            return Some("no channel");
+        };
+
+        // If the guild is not cached, there is no way to tell, so leave it alone.
+        let guild = self.cache.guild(guild_id)?;
+        if !guild.channels.contains_key(&channel_id) {
+            return Some("deleted channel");
+        }
+
+        // Members that are not cached are assumed to be users, to avoid leaving by mistake.
+        let bot_id = self.cache.current_user().id;
+        let has_users = guild
+            .voice_states
+            .values()
+            .filter(|vs| vs.channel_id == Some(channel_id) && vs.user_id != bot_id)
+            .any(|vs| guild.members.get(&vs.user_id).map_or(true, |m| !m.user.bot));
+
+        (!has_users).then_some("alone")
+    }
+}
+
+impl crate::Looper for VoiceSweeper {
+    const NAME: &'static str = "Voice Sweeper";
+    const MILLIS: u64 = 1000 * 60 * 15;
+
+    fn interval(&self) -> Duration {
+        self.interval.map_or_else(
+            || Duration::from_millis(Self::MILLIS),
+            |mins| Duration::from_secs(mins.get() * 60),
+        )
+    }
+
+    async fn loop_func(&self) -> Result<()> {
+        let calls: Vec<_> = self.data.songbird.iter().collect();
+
+        let mut reaped = 0;
+        for (guild_id, call_lock) in calls {
+            let guild_id: serenity::GuildId = guild_id.0.into();
+
+            // A call that is still joining has no channel yet, so must not be swept.
+            let join_vc_lock = JoinVCToken::acquire(&self.data, guild_id);
+            let Ok(_join_vc_token) = join_vc_lock.try_lock() else {
+                continue;
+            };
+
+            let channel_id = call_lock.lock().await.current_channel().map(|c| c.0.into());
+            let Some(reason) = self.orphan_reason(guild_id, channel_id) else {
+                continue;
+            };
+
+            tracing::debug!("{}: Leaving {guild_id}, {reason}", Self::NAME);
+            if let Err(err) = self.data.songbird.remove(guild_id).await {
+                tracing::warn!("{}: Failed to leave {guild_id}: {err:?}", Self::NAME);
+            }
+
+            self.data.last_to_xsaid_tracker.remove(&guild_id);
+            self.data.connected_channels.remove(&guild_id);
+            self.data.last_spoken.remove(&guild_id);
+            reaped += 1;
+        }
+
+        tracing::info!("{}: Reaped {reaped} orphaned voice connections", Self::NAME);
+        Ok(())
+    }
+}